@@ -4,17 +4,93 @@ class TerrainGenerator {
   constructor (seed=123456) {
     this.wasm = new Promise((resolve, reject) => wasm()
       .then(result => {
-        this.terrainGen = new result.TerrainGenerator(seed);
+        this.terrainGen = new result.TerrainGenerator(BigInt(seed));
+        this.token = this.terrainGen.cancellationToken();
+        this.VertexMode = result.VertexMode;
         resolve(true);
       }).catch(reject)
     );
   }
 
-  async generate ({ points = 2**10, seaLevel = 0.39 }={}) {
+  async generate ({ points = 2**10, seaLevel = 0.39, onProgress = null, width = 1, height = 1, scale = 1, wrap = false, planet = false, tile = null, vertexMode = 'Centroid', relax = {}, noise = null, masks = [], heightmap = null, bathymetry = null, glaciation = null, distances = null, derivatives = null, circulation = null }={}) {
     await this.wasm;
+    this.terrainGen.onProgress(onProgress);
+    this.terrainGen.setDomain(width, height, scale);
+    this.terrainGen.setWrap(wrap);
+    // 'Centroid', 'Circumcenter', 'Incenter' or 'ClampedCircumcenter'
+    this.terrainGen.setVertexMode(this.VertexMode[vertexMode]);
+    // Lloyd relaxation, off with 0 iterations. Ignored for `planet` and `tile`.
+    const { iterations = 0, strength = 1, weighted = true } = relax;
+    this.terrainGen.setRelaxation(iterations, strength, weighted);
+    // A noise graph, see `noise.rs`. `null` for the default.
+    this.terrainGen.setNoiseGraph(noise ? JSON.stringify(noise) : undefined);
+    // Where land may appear, see `masks.rs`. Grayscale images as `{ image: { width, height, data } }`.
+    // Ignored for `planet` and `tile`.
+    this.terrainGen.clearMasks();
+    for (const { strength = 1, image, ...mask } of masks) {
+      if (image) {
+        this.terrainGen.addImageMask(image.width, image.height, Float64Array.from(image.data), strength);
+      } else {
+        this.terrainGen.addMask(JSON.stringify(mask), strength);
+      }
+    }
+    // A painted heightmap instead of noise, as `{ png }` bytes or `{ raw, width, height, bits }`.
+    // `noise` from 0 to 1 keeps some of the noise. Ignored for `planet` and `tile`.
+    if (!heightmap) {
+      this.terrainGen.clearHeightmap();
+    } else if (heightmap.png) {
+      this.terrainGen.importHeightmap(heightmap.png, heightmap.noise || 0);
+    } else {
+      const { raw, width, height, bits = 16, noise = 0 } = heightmap;
+      this.terrainGen.importRawHeightmap(width, height, raw, bits, noise);
+    }
+    // Continental shelves, slopes and trenches, see `bathymetry.rs`. `{}` for the defaults, `null`
+    // to leave the sea floor as it erodes. Depths end up in `world.layers.depth`. Ignored for `tile`.
+    this.terrainGen.setBathymetry(bathymetry ? JSON.stringify(bathymetry) : undefined);
+    // Ice caps, U-shaped valleys and fjords, see `glaciation.rs`. `{ north, south }` are the
+    // latitudes of the top and bottom of a flat map. Ice cover per cell ends up in `world.layers.ice`.
+    // Ignored for `tile`.
+    this.terrainGen.setGlaciation(glaciation ? JSON.stringify(glaciation) : undefined);
+    // Distance to the coast, rivers and mountains per cell, in `world.layers`. `{ mountains }` is
+    // the highest fraction of the land counted as mountains. `Number.MAX_VALUE` where there is
+    // nothing to reach, negated at sea for the coast. Ignored for `tile`.
+    const { mountains = 0.05 } = distances || {};
+    this.terrainGen.setDistanceFields(!!distances, mountains);
+    // Slope, aspect, curvature and hillshade in `world.relief`, lit from `{ azimuth, altitude }`.
+    // Ignored for `tile`.
+    const { azimuth = 315, altitude = 45, exaggeration = 1 } = derivatives || {};
+    this.terrainGen.setDerivatives(!!derivatives, azimuth, altitude, exaggeration);
+    // Winds and ocean currents in `world.circulation`. `{ north, south }` are the latitudes of the
+    // top and bottom of a flat map. Ignored for `tile`.
+    const winds = { north: 90, south: -90, terrain: 1, iterations: 8, ...circulation };
+    this.terrainGen.setCirculation(!!circulation, winds.north, winds.south, winds.terrain, winds.iterations);
 
-    let radius = Math.pow(500 / points, 0.5) / 10;
-    const world = this.terrainGen.world(radius, seaLevel).as_js_value();
+    let pipeline;
+    if (planet) {
+      // A unit sphere has an area of 4π.
+      let radius = Math.pow(500 * 4 * Math.PI / points, 0.5) / 10;
+      pipeline = this.terrainGen.planetPipeline(radius, seaLevel);
+    } else {
+      let radius = Math.pow(500 * width * height / points, 0.5) / 10;
+      // Tiles `{ x, y }` of an endless map line up with the tiles generated before them.
+      pipeline = tile
+        ? this.terrainGen.tilePipeline(tile.x, tile.y, radius, seaLevel)
+        : this.terrainGen.pipeline(radius, seaLevel);
+    }
+    // One stage, or one erosion iteration, at a time, handing the event loop back in between so
+    // the page stays responsive and `cancel` gets to run. A cancelled generation throws.
+    try {
+      while (pipeline.step()) {
+        await new Promise(resolve => setTimeout(resolve, 0));
+      }
+    } catch (error) {
+      pipeline.free();
+      throw error;
+    } finally {
+      this.token.reset();
+    }
+    let handle = pipeline.finish();
+    let world = handle.as_js_value();
 
     world.seaLevel = seaLevel;
     world.points           = world.voronoi.delaunay.points;
@@ -24,8 +100,19 @@ class TerrainGenerator {
     world.voronoiPoints    = world.voronoi.voronoi_points;
 
     delete world.voronoi
+    // For mouse picking, in world units. `undefined` off the map.
+    world.cellAt   = (x, y) => handle.cellAt(x, y);
+    world.heightAt = (x, y) => handle.heightAt(x, y);
+    // Contour lines every `interval` from sea level, see `contours.rs`. Throws on intervals so fine
+    // they would give more than 10 000 levels.
+    world.contours = (interval, indexEvery = 5) => handle.contours(seaLevel, interval, indexEvery);
     return world;
   }
+
+  // Stops the generation in progress, or the next one if called before it starts.
+  cancel () {
+    if (this.token) this.token.cancel();
+  }
 }
 
 export default TerrainGenerator;
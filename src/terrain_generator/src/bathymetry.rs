@@ -0,0 +1,173 @@
+use bracket_random::prelude::RandomNumberGenerator;
+
+use super::distance::distances;
+use super::error::TerrainError;
+use super::masks::smoothstep;
+use super::progress::Progress;
+use super::voronoi::Voronoi;
+
+// How the sea floor falls away from the coast: a shallow continental shelf, a steeper slope down
+// from the shelf break, then a flat abyssal plain, with a few trenches along the foot of the slope
+// and seamounts out on the plain. Widths are in world units (radians on a planet) and depths are
+// below sea level, in the same units as heights.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Bathymetry {
+    pub shelf_width: f64,
+    pub shelf_depth: f64,
+    pub slope_width: f64,
+    pub abyssal_depth: f64,
+    // How much of the eroded noise shows through, from 0 (none) to 1 (all of it).
+    pub roughness: f64,
+    pub trenches: usize,
+    pub trench_depth: f64,
+    pub trench_width: f64,
+    pub trench_length: f64,
+    pub seamounts: usize,
+    pub seamount_height: f64,
+    pub seamount_radius: f64,
+}
+
+impl Default for Bathymetry {
+    fn default() -> Bathymetry {
+        Bathymetry {
+            shelf_width: 0.04,
+            shelf_depth: 0.04,
+            slope_width: 0.06,
+            abyssal_depth: 0.25,
+            roughness: 0.25,
+            trenches: 1,
+            trench_depth: 0.1,
+            trench_width: 0.02,
+            trench_length: 0.3,
+            seamounts: 3,
+            seamount_height: 0.15,
+            seamount_radius: 0.04,
+        }
+    }
+}
+
+// Sea corners never come up closer to the surface than this.
+const MIN_DEPTH: f64 = 1e-3;
+
+impl Bathymetry {
+    pub fn validate(&self) -> Result<(), TerrainError> {
+        let widths = [
+            self.shelf_width,
+            self.slope_width,
+            self.trench_width,
+            self.trench_length,
+            self.seamount_radius,
+        ];
+        let depths = [
+            self.shelf_depth,
+            self.abyssal_depth,
+            self.trench_depth,
+            self.seamount_height,
+        ];
+        if !widths.iter().all(|&w| w.is_finite() && w > 0.) {
+            return Err(TerrainError::InvalidBathymetry(
+                "widths, lengths and radii must be positive".to_string(),
+            ));
+        }
+        if !depths.iter().all(|&d| d.is_finite() && d >= 0.) {
+            return Err(TerrainError::InvalidBathymetry(
+                "depths and heights must not be negative".to_string(),
+            ));
+        }
+        if !(0. ..=1.).contains(&self.roughness) {
+            return Err(TerrainError::InvalidBathymetry(format!(
+                "roughness must be between 0 and 1, got {}",
+                self.roughness
+            )));
+        }
+        Ok(())
+    }
+
+    // Depth of the smooth sea floor at `distance` from the nearest land.
+    pub fn profile(&self, distance: f64) -> f64 {
+        if distance <= self.shelf_width {
+            self.shelf_depth * distance / self.shelf_width
+        } else {
+            let t = (distance - self.shelf_width) / self.slope_width;
+            self.shelf_depth + (self.abyssal_depth - self.shelf_depth) * smoothstep(t)
+        }
+    }
+
+    // Reshapes the corner heights below `sea_level`, `length(a, b)` apart along
+    // `Voronoi::adjacent`. Corners of cells with land in them are left alone, so no coast moves.
+    // Stops early if `progress` is cancelled.
+    pub fn shape<F: Fn(usize, usize) -> f64>(
+        &self,
+        heights: &mut [f64],
+        voronoi: &Voronoi,
+        sea_level: f64,
+        length: F,
+        rng: &mut RandomNumberGenerator,
+        progress: &Progress,
+    ) -> Result<(), TerrainError> {
+        let adjacent = &voronoi.adjacent;
+        let land = (0..heights.len())
+            .filter(|&t| heights[t] >= sea_level)
+            .collect::<Vec<_>>();
+        let mut pinned = vec![false; heights.len()];
+        for corners in voronoi.voronoi_points.iter() {
+            if corners.iter().any(|&t| heights[t] >= sea_level) {
+                corners.iter().for_each(|&t| pinned[t] = true);
+            }
+        }
+
+        let coast = distances(adjacent, &land, f64::INFINITY, &length, progress)?;
+        let mut depths = heights
+            .iter()
+            .zip(coast.iter())
+            .map(|(&height, &distance)| {
+                let smooth = self.profile(distance);
+                smooth + self.roughness * (sea_level - height - smooth)
+            })
+            .collect::<Vec<_>>();
+        let at_sea = |t: usize| !pinned[t];
+        let bump = |t: f64| smoothstep(1. - t);
+
+        // Trenches follow the coast, at the foot of the slope.
+        let foot = self.shelf_width + self.slope_width;
+        let candidates = (0..heights.len())
+            .filter(|&t| at_sea(t) && (foot..foot + 2. * self.trench_width).contains(&coast[t]))
+            .collect::<Vec<_>>();
+        for _ in 0..self.trenches {
+            let start = match pick(&candidates, rng) {
+                Some(&t) => t,
+                None => break,
+            };
+            let along = distances(adjacent, &[start], self.trench_length, &length, progress)?;
+            for t in (0..heights.len()).filter(|&t| at_sea(t) && along[t].is_finite()) {
+                let across = (coast[t] - coast[start]).abs() / self.trench_width;
+                depths[t] += self.trench_depth * bump(across) * bump(along[t] / self.trench_length);
+            }
+        }
+
+        let candidates = (0..heights.len())
+            .filter(|&t| at_sea(t) && coast[t] > foot)
+            .collect::<Vec<_>>();
+        for _ in 0..self.seamounts {
+            let peak = match pick(&candidates, rng) {
+                Some(&t) => t,
+                None => break,
+            };
+            let around = distances(adjacent, &[peak], self.seamount_radius, &length, progress)?;
+            for t in (0..heights.len()).filter(|&t| at_sea(t) && around[t].is_finite()) {
+                depths[t] -= self.seamount_height * bump(around[t] / self.seamount_radius);
+            }
+        }
+
+        for t in (0..heights.len()).filter(|&t| at_sea(t)) {
+            heights[t] = sea_level - depths[t].max(MIN_DEPTH);
+        }
+        Ok(())
+    }
+}
+
+fn pick<'a>(candidates: &'a [usize], rng: &mut RandomNumberGenerator) -> Option<&'a usize> {
+    let k = (rng.rand::<f64>() * candidates.len() as f64) as usize;
+    candidates.get(k.min(candidates.len().saturating_sub(1)))
+}
@@ -0,0 +1,164 @@
+use super::derivatives::{gradient, Nodes};
+
+// Settings for `Circulation`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Winds {
+    // Latitudes of the top and bottom edges of a flat map, in degrees. Planets have their own.
+    pub north: f64,
+    pub south: f64,
+    // How hard slopes turn the wind aside, 0 for not at all.
+    pub terrain: f64,
+    // Rounds of evening out winds and currents between neighbouring cells.
+    pub iterations: usize,
+}
+
+impl Default for Winds {
+    fn default() -> Winds {
+        Winds {
+            north: 90.,
+            south: -90.,
+            terrain: 1.,
+            iterations: 8,
+        }
+    }
+}
+
+// Coarse surface winds and ocean currents per cell, as `[x, y]` pairs in the directions of the
+// map's axes, east and south. The strongest prevailing winds are 1 long, and currents about half
+// as strong. There are no currents on land.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Circulation {
+    pub wind: Vec<f64>,
+    pub currents: Vec<f64>,
+}
+
+// The prevailing wind at `latitude` degrees, as `(east, north)`: trade winds blowing west towards
+// the equator, westerlies blowing east towards the poles, polar easterlies, and calms between
+// them.
+pub fn prevailing_wind(latitude: f64) -> (f64, f64) {
+    let band = libm::sin(6. * latitude.abs().to_radians());
+    (-band, -0.5 * band * latitude.signum())
+}
+
+impl Circulation {
+    pub fn new(
+        winds: &Winds,
+        nodes: &Nodes,
+        neighbors: &[Vec<usize>],
+        heights: &[f64],
+        sea_level: f64,
+        latitudes: &[f64],
+    ) -> Circulation {
+        let n = heights.len();
+        let is_land = |i: usize| heights[i] >= sea_level;
+
+        // Which way is uphill on land, and how much of the wind blowing up it is turned aside.
+        let slopes = (0..n)
+            .map(|i| {
+                let (p, q) = gradient(nodes, &neighbors[i], heights, i);
+                let slope = libm::hypot(p, q);
+                if is_land(i) && slope > 0. {
+                    (
+                        (p / slope, q / slope),
+                        1. - libm::exp(-winds.terrain * slope),
+                    )
+                } else {
+                    ((0., 0.), 0.)
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut wind = latitudes
+            .iter()
+            .map(|&latitude| prevailing_wind(latitude))
+            .collect::<Vec<_>>();
+        for _ in 0..winds.iterations {
+            wind = even_out(&wind, neighbors, |_| true);
+            for (w, &(uphill, blocking)) in wind.iter_mut().zip(slopes.iter()) {
+                let up = w.0 * uphill.0 + w.1 * uphill.1;
+                if up > 0. {
+                    w.0 -= blocking * up * uphill.0;
+                    w.1 -= blocking * up * uphill.1;
+                }
+            }
+        }
+
+        // Wind drags the sea along at an angle, to the right in the north and the left in the
+        // south. Coasts stop it flowing any further inland, so it runs along them instead.
+        let mut currents = (0..n)
+            .map(|i| {
+                if is_land(i) {
+                    return (0., 0.);
+                }
+                let turn = -std::f64::consts::FRAC_PI_4 * latitudes[i].signum();
+                let (sin, cos) = (libm::sin(turn), libm::cos(turn));
+                let (east, north) = wind[i];
+                (
+                    0.5 * (east * cos - north * sin),
+                    0.5 * (east * sin + north * cos),
+                )
+            })
+            .collect::<Vec<_>>();
+        let shores = (0..n)
+            .map(|i| {
+                neighbors[i]
+                    .iter()
+                    .filter(|&&j| !is_land(i) && is_land(j))
+                    .map(|&j| {
+                        let (x, y) = nodes.offset(i, j);
+                        let length = libm::hypot(x, y);
+                        (x / length, y / length)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..winds.iterations.max(1) {
+            currents = even_out(&currents, neighbors, |i| !is_land(i));
+            for (c, shore) in currents.iter_mut().zip(shores.iter()) {
+                for &(x, y) in shore.iter() {
+                    let inland = c.0 * x + c.1 * y;
+                    if inland > 0. {
+                        c.0 -= inland * x;
+                        c.1 -= inland * y;
+                    }
+                }
+            }
+        }
+
+        let to_map = |vectors: Vec<(f64, f64)>| {
+            vectors
+                .into_iter()
+                .flat_map(|(east, north)| [east, -north])
+                .collect()
+        };
+        Circulation {
+            wind: to_map(wind),
+            currents: to_map(currents),
+        }
+    }
+}
+
+// Moves each vector half way to the mean of its neighbours', among the cells `flows` lets
+// through. The rest are left as they are.
+fn even_out<F: Fn(usize) -> bool>(
+    vectors: &[(f64, f64)],
+    neighbors: &[Vec<usize>],
+    flows: F,
+) -> Vec<(f64, f64)> {
+    (0..vectors.len())
+        .map(|i| {
+            let (mut x, mut y, mut count) = (0., 0., 0.);
+            for &j in neighbors[i].iter().filter(|&&j| flows(j)) {
+                x += vectors[j].0;
+                y += vectors[j].1;
+                count += 1.;
+            }
+            if !flows(i) || count == 0. {
+                return vectors[i];
+            }
+            (
+                (vectors[i].0 + x / count) / 2.,
+                (vectors[i].1 + y / count) / 2.,
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use super::error::TerrainError;
+use super::sphere::{self, Sphere};
+use super::terrain_generator::World;
+use super::voronoi::Voronoi;
+
+// An isoline through the heights of the `voronoi_triangles` fans, the same surface `heightAt`
+// interpolates.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Contour {
+    pub height: f64,
+    // Every so many levels, for drawing heavier and labelling, see `contours`.
+    pub index: bool,
+    // Loops back from the last point to the first.
+    pub closed: bool,
+    // `[x0, y0, x1, y1, ...]` on the map.
+    pub points: Vec<f64>,
+}
+
+// Any finer an interval, and drawing the contours takes longer than it is worth.
+pub const MAX_LEVELS: i64 = 10_000;
+
+// Contours at every `interval` above and below `base`, usually the sea level. Every
+// `index_every`th one, counting from `base`, is an index contour. Lines are broken where they
+// leave the map, or cross the seam of a wrapping map or a planet. Fails if there would be more than
+// `MAX_LEVELS` between the lowest and highest points.
+pub fn contours(
+    world: &World,
+    base: f64,
+    interval: f64,
+    index_every: usize,
+) -> Result<Vec<Contour>, TerrainError> {
+    if !(interval.is_finite() && interval > 0. && base.is_finite()) {
+        return Err(TerrainError::InvalidContours { base, interval });
+    }
+    let World {
+        voronoi,
+        heights,
+        cell_heights,
+        sphere,
+        bounds,
+        index,
+        ..
+    } = world;
+    let sphere = sphere.as_ref();
+    let width = if sphere.is_some() || index.wraps() {
+        Some(bounds[2] - bounds[0])
+    } else {
+        None
+    };
+    let fans = &voronoi.voronoi_triangles;
+    if fans.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Cells and corners are both nodes of the fans, corners numbered after the cells.
+    let n = cell_heights.len();
+    let height = |node: usize| {
+        if node < n {
+            cell_heights[node]
+        } else {
+            heights[node - n]
+        }
+    };
+    let (low, high) = fans
+        .chunks_exact(3)
+        .flat_map(|fan| [fan[0], fan[1] + n, fan[2] + n])
+        .map(height)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), h| {
+            (low.min(h), high.max(h))
+        });
+
+    // Levels are counted in steps from `base`, those from `low` up to `high`.
+    let steps = |low: f64, high: f64| {
+        let first = libm::ceil((low - base) / interval) as i64;
+        let last = libm::floor((high - base) / interval) as i64;
+        first..=last
+    };
+    let levels = steps(low, high);
+    let (first, last) = (*levels.start(), *levels.end());
+    if last.saturating_sub(first) >= MAX_LEVELS {
+        return Err(TerrainError::InvalidContours { base, interval });
+    }
+    let level = |k: i64| base + k as f64 * interval;
+
+    // Each fan has a segment between two of its edges for every level that crosses it.
+    let mut segments = vec![Vec::new(); (last - first + 1).max(0) as usize];
+    for fan in fans.chunks_exact(3) {
+        let nodes = [fan[0], fan[1] + n, fan[2] + n];
+        let fan_heights = nodes.map(height);
+        let crossing_levels = steps(
+            fan_heights.iter().copied().fold(f64::INFINITY, f64::min),
+            fan_heights
+                .iter()
+                .copied()
+                .fold(f64::NEG_INFINITY, f64::max),
+        );
+        for k in crossing_levels {
+            let above = |node: usize| height(node) >= level(k);
+            let crossed = (0..3)
+                .map(|e| (nodes[e], nodes[(e + 1) % 3]))
+                .filter(|&(a, b)| above(a) != above(b))
+                .map(|(a, b)| (a.min(b), a.max(b)))
+                .collect::<Vec<_>>();
+            if let [a, b] = crossed[..] {
+                segments[(k - first) as usize].push([a, b]);
+            }
+        }
+    }
+
+    let mut contours = Vec::new();
+    for (k, segments) in levels.zip(segments.iter()) {
+        let level = level(k);
+        let index = index_every > 0 && k.rem_euclid(index_every as i64) == 0;
+
+        for (edges, closed) in stitch(segments) {
+            let points = edges
+                .iter()
+                .map(|&(a, b)| {
+                    let t = (level - height(a)) / (height(b) - height(a));
+                    crossing(voronoi, sphere, width, n, a, b, t)
+                })
+                .collect::<Vec<_>>();
+            let pieces = split(points, closed, width)
+                .into_iter()
+                .flat_map(|(points, closed)| clip(points, closed, *bounds));
+            for (points, closed) in pieces {
+                contours.push(Contour {
+                    height: level,
+                    index,
+                    closed,
+                    points: points.iter().flatten().copied().collect(),
+                });
+            }
+        }
+    }
+    Ok(contours)
+}
+
+// Chains segments that share an edge into lines of edges, open ones first from their ends.
+fn stitch(segments: &[[(usize, usize); 2]]) -> Vec<(Vec<(usize, usize)>, bool)> {
+    let mut by_edge: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (s, segment) in segments.iter().enumerate() {
+        for &edge in segment.iter() {
+            by_edge.entry(edge).or_default().push(s);
+        }
+    }
+    let is_end = |edge: &(usize, usize)| by_edge[edge].len() == 1;
+
+    let mut used = vec![false; segments.len()];
+    let mut lines = Vec::new();
+    let starts = (0..segments.len())
+        .filter(|&s| segments[s].iter().any(is_end))
+        .chain(0..segments.len());
+    for s in starts {
+        if used[s] {
+            continue;
+        }
+        let [a, b] = segments[s];
+        let (start, mut edge) = if is_end(&b) && !is_end(&a) {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        let mut line = vec![start, edge];
+        let mut closed = false;
+        used[s] = true;
+        let mut current = s;
+        loop {
+            let next = by_edge[&edge]
+                .iter()
+                .copied()
+                .find(|&other| other != current && !used[other]);
+            match next {
+                Some(next) => {
+                    used[next] = true;
+                    let [a, b] = segments[next];
+                    edge = if a == edge { b } else { a };
+                    current = next;
+                    if edge == start {
+                        closed = true;
+                        break;
+                    }
+                    line.push(edge);
+                }
+                None => break,
+            }
+        }
+        lines.push((line, closed));
+    }
+    lines
+}
+
+// Where the level crosses from node `a` to node `b`, `t` of the way along.
+fn crossing(
+    voronoi: &Voronoi,
+    sphere: Option<&Sphere>,
+    width: Option<f64>,
+    n: usize,
+    a: usize,
+    b: usize,
+    t: f64,
+) -> [f64; 2] {
+    match sphere {
+        Some(sphere) => {
+            let position = |node: usize| {
+                if node < n {
+                    &sphere.positions[node * 3..node * 3 + 3]
+                } else {
+                    &sphere.circumcenters[(node - n) * 3..(node - n) * 3 + 3]
+                }
+            };
+            let (p, q) = (position(a), position(b));
+            let (x, y) = sphere::map_position(&sphere::normalize([
+                p[0] + t * (q[0] - p[0]),
+                p[1] + t * (q[1] - p[1]),
+                p[2] + t * (q[2] - p[2]),
+            ]));
+            [x, y]
+        }
+        None => {
+            let position = |node: usize| {
+                let (positions, i) = if node < n {
+                    (&voronoi.delaunay.points, node)
+                } else {
+                    (&voronoi.circumcenters, node - n)
+                };
+                [positions[i * 2], positions[i * 2 + 1]]
+            };
+            let (p, q) = (position(a), position(b));
+            let mut dx = q[0] - p[0];
+            let mut x = p[0];
+            if let Some(width) = width {
+                dx -= libm::round(dx / width) * width;
+                x = (x + t * dx).rem_euclid(width);
+            } else {
+                x += t * dx;
+            }
+            [x, p[1] + t * (q[1] - p[1])]
+        }
+    }
+}
+
+// Breaks a line wherever it jumps more than half of `width` across the map.
+fn split(points: Vec<[f64; 2]>, closed: bool, width: Option<f64>) -> Vec<(Vec<[f64; 2]>, bool)> {
+    let width = match width {
+        Some(width) => width,
+        None => return vec![(points, closed)],
+    };
+    let jumps = |a: &[f64; 2], b: &[f64; 2]| (a[0] - b[0]).abs() > width / 2.;
+    let m = points.len();
+    let wraps_round = closed && m > 1 && jumps(&points[m - 1], &points[0]);
+    let first = match (1..m).find(|&k| jumps(&points[k - 1], &points[k])) {
+        Some(k) => k,
+        None if wraps_round => 0,
+        None => return vec![(points, closed)],
+    };
+
+    // A loop is opened up at its first jump, so the pieces are all open.
+    let order = if closed {
+        (first..m).chain(0..first).collect::<Vec<_>>()
+    } else {
+        (0..m).collect()
+    };
+    let mut pieces = vec![Vec::new()];
+    for (k, &i) in order.iter().enumerate() {
+        if k > 0 && jumps(&points[order[k - 1]], &points[i]) {
+            pieces.push(Vec::new());
+        }
+        pieces.last_mut().unwrap().push(points[i]);
+    }
+    pieces.into_iter().map(|piece| (piece, false)).collect()
+}
+
+// Cuts a line down to the parts inside `bounds`, ending each at the edge.
+fn clip(points: Vec<[f64; 2]>, closed: bool, bounds: [f64; 4]) -> Vec<(Vec<[f64; 2]>, bool)> {
+    let [xmin, ymin, xmax, ymax] = bounds;
+    let inside = |p: &[f64; 2]| (xmin..=xmax).contains(&p[0]) && (ymin..=ymax).contains(&p[1]);
+    let first = match points.iter().position(|p| !inside(p)) {
+        Some(first) => first,
+        None => return vec![(points, closed)],
+    };
+    // From inside `a` to where the way to `b` leaves the map.
+    let edge = |a: &[f64; 2], b: &[f64; 2]| {
+        let mut t: f64 = 1.;
+        for axis in 0..2 {
+            let (min, max) = (bounds[axis], bounds[axis + 2]);
+            let d = b[axis] - a[axis];
+            if b[axis] > max {
+                t = t.min((max - a[axis]) / d);
+            } else if b[axis] < min {
+                t = t.min((min - a[axis]) / d);
+            }
+        }
+        let x = a[0] + t * (b[0] - a[0]);
+        let y = a[1] + t * (b[1] - a[1]);
+        [x.clamp(xmin, xmax), y.clamp(ymin, ymax)]
+    };
+
+    // A loop is walked all the way round from a point outside.
+    let order = if closed {
+        (first..points.len()).chain(0..=first).collect::<Vec<_>>()
+    } else {
+        (0..points.len()).collect()
+    };
+    let mut pieces = Vec::new();
+    let mut piece = Vec::new();
+    if inside(&points[order[0]]) {
+        piece.push(points[order[0]]);
+    }
+    for pair in order.windows(2) {
+        let (a, b) = (&points[pair[0]], &points[pair[1]]);
+        match (inside(a), inside(b)) {
+            (true, true) => piece.push(*b),
+            (true, false) => {
+                piece.push(edge(a, b));
+                pieces.push(std::mem::take(&mut piece));
+            }
+            (false, true) => {
+                piece.push(edge(b, a));
+                piece.push(*b);
+            }
+            (false, false) => {}
+        }
+    }
+    pieces.push(piece);
+    pieces
+        .into_iter()
+        .filter(|piece| piece.len() > 1)
+        .map(|piece| (piece, false))
+        .collect()
+}
@@ -0,0 +1,64 @@
+use std::rc::Rc;
+
+use super::raster::Raster;
+use super::terrain_generator::TerrainGenerator;
+
+// Below this, sampling would never run out of room for new points.
+const MIN_SPACING: f64 = 0.05;
+
+// How tightly `poisson::disc_sample` packs points. Each mode gives the minimum distance between
+// points relative to the sampling radius: 1 is as sparse as the radius allows, lower is denser.
+// Anything above 1 counts as 1, the sampler only looks for rivals that far out.
+#[derive(Clone, Default)]
+pub enum Density {
+    Uniform,
+    // Denser around sea level, sparser on high ground and in deep sea.
+    #[default]
+    Noise,
+    // Densest (`min`) at (`x`, `y`), back to uniform at `radius` away.
+    Radial {
+        x: f64,
+        y: f64,
+        radius: f64,
+        min: f64,
+    },
+    // Spacing read from a grayscale raster stretched over the map.
+    Mask(Raster),
+    Custom(Rc<dyn Fn(f64, f64) -> f64>),
+}
+
+impl Density {
+    pub fn spacing(&self, x: f64, y: f64, sea_level: f64, gen: &TerrainGenerator) -> f64 {
+        let spacing = match self {
+            Density::Uniform => 1.,
+            Density::Noise => {
+                let h = gen.noise_single(x, y);
+                if h > sea_level {
+                    h
+                } else {
+                    1.0 - h
+                }
+            }
+            Density::Radial {
+                x: cx,
+                y: cy,
+                radius,
+                min,
+            } => {
+                let t = (gen.domain.distance((*cx, *cy), (x, y)) / radius).min(1.);
+                min + (1. - min) * t
+            }
+            Density::Mask(raster) => {
+                let (u, v) = gen.domain.normalize(x, y);
+                raster.sample(u, v)
+            }
+            Density::Custom(spacing) => spacing(x, y),
+        };
+
+        if spacing.is_nan() {
+            1.
+        } else {
+            spacing.clamp(MIN_SPACING, 1.)
+        }
+    }
+}
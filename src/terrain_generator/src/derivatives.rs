@@ -0,0 +1,242 @@
+use super::domain::Domain;
+use super::sphere;
+
+// Where the sun shines from for `Derivatives::hillshade`, as compass degrees clockwise from north
+// and degrees above the horizon. Heights are multiplied by `exaggeration` first, since they aren't
+// in world units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lighting {
+    pub azimuth: f64,
+    pub altitude: f64,
+    pub exaggeration: f64,
+}
+
+impl Default for Lighting {
+    fn default() -> Lighting {
+        Lighting {
+            azimuth: 315.,
+            altitude: 45.,
+            exaggeration: 1.,
+        }
+    }
+}
+
+// The shape of the height field around each node of a mesh. North is up the map, or towards the
+// north pole on a planet.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Derivatives {
+    // Rise over run, in heights per world unit.
+    pub slope: Vec<f64>,
+    // The compass direction downhill faces, in degrees clockwise from north. -1 on flat ground.
+    pub aspect: Vec<f64>,
+    // Curvature across and along the slope, positive where the ground is convex.
+    #[serde(rename = "planCurvature")]
+    pub plan_curvature: Vec<f64>,
+    #[serde(rename = "profileCurvature")]
+    pub profile_curvature: Vec<f64>,
+    // From 0 in shadow to 1 facing the sun, see `Lighting`.
+    pub hillshade: Vec<f64>,
+}
+
+// Derivatives at the voronoi corners, over `Voronoi::adjacent`, and at the points, over
+// `Delaunay::neighbors`.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Relief {
+    pub corners: Derivatives,
+    pub cells: Derivatives,
+}
+
+// Positions of a mesh's nodes: `[x, y]` in `flat`, or `[x, y, z]` on the unit sphere in `round`
+// when there are any.
+pub struct Nodes<'a> {
+    pub flat: &'a [f64],
+    pub round: Option<&'a [f64]>,
+    // Only for wrapping, see `Domain::delta`.
+    pub domain: Domain,
+}
+
+impl Nodes<'_> {
+    // `j` seen from `i`, as `(east, north)`. On a planet, flattened onto the plane touching the
+    // sphere at `i`.
+    pub(crate) fn offset(&self, i: usize, j: usize) -> (f64, f64) {
+        match self.round {
+            Some(positions) => {
+                let (p, q) = (&positions[i * 3..i * 3 + 3], &positions[j * 3..j * 3 + 3]);
+                let mut east = sphere::cross(&[0., 0., 1.], p);
+                if sphere::dot(&east, &east) < 1e-12 {
+                    east = [0., 1., 0.];
+                }
+                let east = sphere::normalize(east);
+                let north = sphere::cross(p, &east);
+                let d = [q[0] - p[0], q[1] - p[1], q[2] - p[2]];
+                (sphere::dot(&d, &east), sphere::dot(&d, &north))
+            }
+            None => {
+                let f = self.flat;
+                let (dx, dy) = self
+                    .domain
+                    .delta((f[i * 2], f[i * 2 + 1]), (f[j * 2], f[j * 2 + 1]));
+                // The map's y axis points south.
+                (dx, -dy)
+            }
+        }
+    }
+}
+
+impl Derivatives {
+    pub fn new(
+        nodes: &Nodes,
+        neighbors: &[Vec<usize>],
+        heights: &[f64],
+        lighting: &Lighting,
+    ) -> Derivatives {
+        let mut derivatives = Derivatives::default();
+        let zenith = (90. - lighting.altitude).to_radians();
+        let azimuth = lighting.azimuth.to_radians();
+
+        for i in 0..heights.len() {
+            let (p, q) = gradient(nodes, &neighbors[i], heights, i);
+            let slope = libm::hypot(p, q);
+            let aspect = if slope > 0. {
+                libm::atan2(-p, -q).to_degrees().rem_euclid(360.)
+            } else {
+                -1.
+            };
+            let (plan, profile) = curvature(nodes, neighbors, heights, i);
+
+            let angle = libm::atan(slope * lighting.exaggeration);
+            let facing = if slope > 0. {
+                libm::cos(azimuth - aspect.to_radians())
+            } else {
+                0.
+            };
+            let shade = libm::cos(zenith) * libm::cos(angle)
+                + libm::sin(zenith) * libm::sin(angle) * facing;
+
+            derivatives.slope.push(slope);
+            derivatives.aspect.push(aspect);
+            derivatives.plan_curvature.push(plan);
+            derivatives.profile_curvature.push(profile);
+            derivatives.hillshade.push(shade.max(0.));
+        }
+        derivatives
+    }
+}
+
+// Least squares plane through the neighbours of `i`, as its `(east, north)` slopes.
+pub(crate) fn gradient(
+    nodes: &Nodes,
+    neighbors: &[usize],
+    heights: &[f64],
+    i: usize,
+) -> (f64, f64) {
+    let offsets = neighbors
+        .iter()
+        .map(|&j| nodes.offset(i, j))
+        .collect::<Vec<_>>();
+    let scale = spacing(&offsets);
+    if scale == 0. {
+        return (0., 0.);
+    }
+
+    let (mut a, mut b) = ([[0.; 2]; 2], [0.; 2]);
+    for (&j, &(x, y)) in neighbors.iter().zip(offsets.iter()) {
+        let terms = [x / scale, y / scale];
+        let dh = heights[j] - heights[i];
+        for row in 0..2 {
+            for col in 0..2 {
+                a[row][col] += terms[row] * terms[col];
+            }
+            b[row] += terms[row] * dh;
+        }
+    }
+    match solve(a, b) {
+        Some([p, q]) => (p / scale, q / scale),
+        None => (0., 0.),
+    }
+}
+
+// Typical distance to the neighbours, to fit in units of so the equations stay well conditioned.
+fn spacing(offsets: &[(f64, f64)]) -> f64 {
+    if offsets.is_empty() {
+        return 0.;
+    }
+    let squares = offsets.iter().map(|&(x, y)| x * x + y * y).sum::<f64>();
+    (squares / offsets.len() as f64).sqrt()
+}
+
+// Plan and profile curvature of a least squares quadratic through the neighbours of `i` and
+// theirs, after Shary's formulas.
+fn curvature(nodes: &Nodes, neighbors: &[Vec<usize>], heights: &[f64], i: usize) -> (f64, f64) {
+    let mut ring = Vec::new();
+    for &j in neighbors[i].iter() {
+        for &k in std::iter::once(&j).chain(neighbors[j].iter()) {
+            if k != i && !ring.contains(&k) {
+                ring.push(k);
+            }
+        }
+    }
+    let offsets = ring.iter().map(|&j| nodes.offset(i, j)).collect::<Vec<_>>();
+    let scale = spacing(&offsets);
+    if ring.len() < 5 || scale == 0. {
+        return (0., 0.);
+    }
+
+    let (mut a, mut b) = ([[0.; 5]; 5], [0.; 5]);
+    for (&j, &(x, y)) in ring.iter().zip(offsets.iter()) {
+        let (x, y) = (x / scale, y / scale);
+        let terms = [x, y, x * x, x * y, y * y];
+        let dh = heights[j] - heights[i];
+        for row in 0..5 {
+            for col in 0..5 {
+                a[row][col] += terms[row] * terms[col];
+            }
+            b[row] += terms[row] * dh;
+        }
+    }
+    let [p, q, r, s, t] = match solve(a, b) {
+        Some(c) => [
+            c[0] / scale,
+            c[1] / scale,
+            2. * c[2] / (scale * scale),
+            c[3] / (scale * scale),
+            2. * c[4] / (scale * scale),
+        ],
+        None => return (0., 0.),
+    };
+
+    let steepness = p * p + q * q;
+    if steepness < 1e-18 {
+        return (0., 0.);
+    }
+    let plan = -(q * q * r - 2. * p * q * s + p * p * t) / libm::pow(steepness, 1.5);
+    let profile =
+        -(p * p * r + 2. * p * q * s + q * q * t) / (steepness * libm::pow(1. + steepness, 1.5));
+    (plan, profile)
+}
+
+// Gaussian elimination with partial pivoting, `None` if `a` is singular.
+fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let largest = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[largest][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, largest);
+        b.swap(col, largest);
+        let pivot = a[col];
+        for row in col + 1..N {
+            let factor = a[row][col] / pivot[col];
+            for (x, &p) in a[row][col..].iter_mut().zip(pivot[col..].iter()) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.; N];
+    for row in (0..N).rev() {
+        let rest = (row + 1..N).map(|k| a[row][k] * x[k]).sum::<f64>();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
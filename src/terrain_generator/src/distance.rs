@@ -0,0 +1,143 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use super::error::TerrainError;
+use super::progress::Progress;
+use super::sphere;
+use super::stage::WorldContext;
+
+// Shortest distances through a graph to the nearest of `sources`, `length(a, b)` along each edge.
+// Anything further than `limit`, or cut off from every source, is left at infinity. Stops early
+// if `progress` is cancelled.
+pub fn distances<F: Fn(usize, usize) -> f64>(
+    neighbors: &[Vec<usize>],
+    sources: &[usize],
+    limit: f64,
+    length: F,
+    progress: &Progress,
+) -> Result<Vec<f64>, TerrainError> {
+    let sources = sources
+        .iter()
+        .map(|&source| (source, 0.))
+        .collect::<Vec<_>>();
+    distances_from(neighbors, &sources, limit, length, progress)
+}
+
+// As `distances`, with each `(source, distance)` starting that far away already.
+pub fn distances_from<F: Fn(usize, usize) -> f64>(
+    neighbors: &[Vec<usize>],
+    sources: &[(usize, f64)],
+    limit: f64,
+    length: F,
+    progress: &Progress,
+) -> Result<Vec<f64>, TerrainError> {
+    let nearest = nearest_from(neighbors, sources, limit, length, progress)?;
+    Ok(nearest
+        .into_iter()
+        .map(|nearest| nearest.map_or(f64::INFINITY, |(_, distance)| distance))
+        .collect())
+}
+
+// As `distances_from`, along with which source is the nearest. `None` where nothing is in reach.
+pub fn nearest_from<F: Fn(usize, usize) -> f64>(
+    neighbors: &[Vec<usize>],
+    sources: &[(usize, f64)],
+    limit: f64,
+    length: F,
+    progress: &Progress,
+) -> Result<Vec<Option<(usize, f64)>>, TerrainError> {
+    let mut nearest: Vec<Option<(usize, f64)>> = vec![None; neighbors.len()];
+    let further = |nearest: Option<(usize, f64)>, distance: f64| {
+        nearest.is_none_or(|(_, known)| distance < known)
+    };
+    let mut queue = BinaryHeap::new();
+    for &(source, distance) in sources.iter() {
+        if further(nearest[source], distance) && distance <= limit {
+            nearest[source] = Some((source, distance));
+            queue.push(Reverse(Entry(distance, source)));
+        }
+    }
+
+    let mut visited = 0;
+    while let Some(Reverse(Entry(distance, i))) = queue.pop() {
+        if visited % 4096 == 0 {
+            progress.check()?;
+        }
+        visited += 1;
+        let (source, known) = nearest[i].expect("queued corners have a source");
+        if distance > known {
+            continue;
+        }
+        for &j in neighbors[i].iter() {
+            let next = distance + length(i, j);
+            if further(nearest[j], next) && next <= limit {
+                nearest[j] = Some((source, next));
+                queue.push(Reverse(Entry(next, j)));
+            }
+        }
+    }
+    Ok(nearest)
+}
+
+// Lengths between the voronoi corners of `Voronoi::adjacent` in the world in progress, in world
+// units, or radians on a planet.
+pub fn corner_lengths(
+    context: &WorldContext,
+) -> Result<impl Fn(usize, usize) -> f64 + '_, TerrainError> {
+    Ok(lengths(
+        context,
+        &context.voronoi()?.circumcenters,
+        context
+            .sphere
+            .as_ref()
+            .map(|sphere| &sphere.circumcenters[..]),
+    ))
+}
+
+// Lengths between the points of `Delaunay::neighbors` in the world in progress, as
+// `corner_lengths`.
+pub fn point_lengths(
+    context: &WorldContext,
+) -> Result<impl Fn(usize, usize) -> f64 + '_, TerrainError> {
+    Ok(lengths(
+        context,
+        &context.voronoi()?.delaunay.points,
+        context.sphere.as_ref().map(|sphere| &sphere.positions[..]),
+    ))
+}
+
+// `[x, y]` positions, or `[x, y, z]` on the unit sphere when there are any. Wrapping maps are
+// measured the short way across the seam.
+fn lengths<'a>(
+    context: &'a WorldContext,
+    flat: &'a [f64],
+    round: Option<&'a [f64]>,
+) -> impl Fn(usize, usize) -> f64 + 'a {
+    let mut domain = context.domain;
+    domain.wrap = domain.wrap && context.tile.is_none();
+    move |a, b| match round {
+        Some(positions) => sphere::arc(&positions[a * 3..a * 3 + 3], &positions[b * 3..b * 3 + 3]),
+        None => domain.distance(
+            (flat[a * 2], flat[a * 2 + 1]),
+            (flat[b * 2], flat[b * 2 + 1]),
+        ),
+    }
+}
+
+// Nearest first, ties by index.
+#[derive(PartialEq)]
+struct Entry(f64, usize);
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
@@ -0,0 +1,78 @@
+use super::error::TerrainError;
+
+// The rectangle `[0, width] × [0, height]` the world covers, in world units. Noise features are
+// `scale` world units across, so a bigger map at the same scale shows more terrain, not larger.
+// With `wrap` set, the east and west edges meet, like a map of a cylinder.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Domain {
+    pub width: f64,
+    pub height: f64,
+    pub scale: f64,
+    pub wrap: bool,
+}
+
+impl Default for Domain {
+    fn default() -> Domain {
+        Domain {
+            width: 1.,
+            height: 1.,
+            scale: 1.,
+            wrap: false,
+        }
+    }
+}
+
+impl Domain {
+    pub fn new(width: f64, height: f64, scale: f64) -> Result<Domain, TerrainError> {
+        let valid = |x: f64| x.is_finite() && x > 0.;
+        if !(valid(width) && valid(height) && valid(scale)) {
+            return Err(TerrainError::InvalidDomain {
+                width,
+                height,
+                scale,
+            });
+        }
+        Ok(Domain {
+            width,
+            height,
+            scale,
+            wrap: false,
+        })
+    }
+
+    // `[xmin, ymin, xmax, ymax]`
+    pub fn bounds(&self) -> [f64; 4] {
+        [0., 0., self.width, self.height]
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.width / 2., self.height / 2.)
+    }
+
+    pub fn shortest_side(&self) -> f64 {
+        self.width.min(self.height)
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        (0. ..=self.width).contains(&x) && (0. ..=self.height).contains(&y)
+    }
+
+    // Vector from `a` to `b`, taking the short way across the seam of a wrapping map.
+    pub fn delta(&self, a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+        let mut dx = b.0 - a.0;
+        if self.wrap {
+            dx -= (dx / self.width).round() * self.width;
+        }
+        (dx, b.1 - a.1)
+    }
+
+    pub fn distance(&self, a: (f64, f64), b: (f64, f64)) -> f64 {
+        let (dx, dy) = self.delta(a, b);
+        libm::hypot(dx, dy)
+    }
+
+    // Position within the domain as fractions of its width and height, e.g. for sampling rasters.
+    pub fn normalize(&self, x: f64, y: f64) -> (f64, f64) {
+        (x / self.width, y / self.height)
+    }
+}
@@ -1,33 +1,59 @@
-pub fn get_flux(heights: &Vec<f64>, adjacent: &Vec<Vec<usize>>) -> Vec<f64> {
+use super::domain::Domain;
+use super::error::TerrainError;
+
+// Heights get sorted and compared all over the place, which only makes sense without NaNs.
+pub fn check_heights(heights: &[f64]) -> Result<(), TerrainError> {
+    match heights.iter().position(|height| height.is_nan()) {
+        Some(i) => Err(TerrainError::NanHeight(i)),
+        None => Ok(()),
+    }
+}
+
+pub fn get_flux(heights: &Vec<f64>, adjacent: &Vec<Vec<usize>>) -> Result<Vec<f64>, TerrainError> {
+    check_heights(heights)?;
     let mut flux = vec![0.0; heights.len()];
 
     let mut sorted = (0..heights.len()).collect::<Vec<usize>>();
-    sorted.sort_unstable_by(|a, b| heights[*a].partial_cmp(&heights[*b]).unwrap().reverse());
+    sorted.sort_unstable_by(|a, b| heights[*a].total_cmp(&heights[*b]).reverse());
 
     // find downhill for each point.
     for &point in sorted.iter() {
+        if adjacent[point].len() <= 2 {
+            continue;
+        }
         let lowest_neighbour: usize = *adjacent[point]
             .iter()
-            .min_by(|a, b| heights[**a].partial_cmp(&heights[**b]).unwrap())
-            .unwrap();
+            .min_by(|a, b| heights[**a].total_cmp(&heights[**b]))
+            .expect("more than two neighbours");
 
-        if adjacent[point].len() > 2 && heights[lowest_neighbour] < heights[point] {
+        if heights[lowest_neighbour] < heights[point] {
             flux[lowest_neighbour] += flux[point] + 1.0;
         }
     }
-    flux
+    Ok(flux)
 }
 
-pub fn fill_sinks(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> Vec<f64> {
+// Water drains into the sea and off any `drains`, such as the cut edge of a tile.
+pub fn fill_sinks(
+    heights: Vec<f64>,
+    adjacent: &Vec<Vec<usize>>,
+    sea_level: f64,
+    drains: &[usize],
+) -> Result<Vec<f64>, TerrainError> {
+    check_heights(&heights)?;
     // Mewo implementation details: https://mewo2.com/notes/terrain/
     // Original paper: https://horizon.documentation.ird.fr/exl-doc/pleins_textes/pleins_textes_7/sous_copyright/010031925.pdf
     let epsilon = 1e-5;
 
+    let mut is_drain = vec![false; heights.len()];
+    drains.iter().for_each(|&i| is_drain[i] = true);
+
     let mut new_heights: Vec<f64> = heights
         .clone()
         .iter()
-        .map(|&height| {
-            if height > sea_level {
+        .zip(is_drain.iter())
+        .map(|(&height, &is_drain)| {
+            if height > sea_level && !is_drain {
                 f64::INFINITY
             } else {
                 height
@@ -36,7 +62,7 @@ pub fn fill_sinks(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64)
         .collect();
 
     let mut sorted: Vec<(usize, f64)> = heights.clone().into_iter().enumerate().collect();
-    sorted.sort_unstable_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+    sorted.sort_unstable_by(|(_, a), (_, b)| a.total_cmp(b));
 
     let mut changed = true;
     while changed {
@@ -65,12 +91,16 @@ pub fn fill_sinks(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64)
         }
     }
 
-    new_heights
+    Ok(new_heights)
 }
 
-pub fn plateau(points: &Vec<f64>, mut heights: Vec<f64>) -> Vec<f64> {
+pub fn plateau(points: &[f64], mut heights: Vec<f64>, domain: &Domain) -> Vec<f64> {
+    if heights.is_empty() {
+        return heights;
+    }
     let plateau_start = 0.45; // Magic
     let plateau_cap = (1. - plateau_start) / 4.; // Magic
+    let radius = domain.shortest_side() / 2.;
 
     let mut peak_index = 0;
     for (j, &height) in heights.iter().enumerate() {
@@ -92,19 +122,25 @@ pub fn plateau(points: &Vec<f64>, mut heights: Vec<f64>) -> Vec<f64> {
         let x = points[i * 2 + 0];
         let y = points[i * 2 + 1];
 
-        let distance_to_peak = ((x - peak_x).hypot(y - peak_y).min(0.5) / 0.5).powi(2);
+        let distance_to_peak =
+            (domain.distance((peak_x, peak_y), (x, y)).min(radius) / radius).powi(2);
         heights[i] = (1. - distance_to_peak) * height + distance_to_peak * interpolate(height);
     }
 
     heights
 }
 
-pub fn erode(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> Vec<f64> {
+pub fn erode(
+    heights: Vec<f64>,
+    adjacent: &Vec<Vec<usize>>,
+    sea_level: f64,
+    drains: &[usize],
+) -> Result<Vec<f64>, TerrainError> {
     // let heights = smooth_coasts(heights, adjacent, sea_level);
     let heights = smooth(heights, adjacent);
-    let heights = fill_sinks(heights, adjacent, sea_level);
+    let heights = fill_sinks(heights, adjacent, sea_level, drains)?;
 
-    let flux = get_flux(&heights, adjacent);
+    let flux = get_flux(&heights, adjacent)?;
     // let n = heights.len() as f64;
 
     let erosion_rate = 0.015;
@@ -131,7 +167,7 @@ pub fn erode(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> V
         .collect::<Vec<Vec<f64>>>();
 
     let erosion = |(i, height): (usize, f64)| {
-        let point_flux = (flux[i] + 1.).ln();
+        let point_flux = libm::log(flux[i] + 1.);
 
         let erosion = point_flux * erosion_rate * height;
 
@@ -157,7 +193,7 @@ pub fn erode(heights: Vec<f64>, adjacent: &Vec<Vec<usize>>, sea_level: f64) -> V
         .map(erosion)
         .collect::<Vec<f64>>();
 
-    heights
+    Ok(heights)
 }
 
 pub fn smooth(mut heights: Vec<f64>, adjacent: &Vec<Vec<usize>>) -> Vec<f64> {
@@ -188,7 +224,8 @@ pub fn smooth_coasts(
     mut heights: Vec<f64>,
     adjacent: &Vec<Vec<usize>>,
     sea_level: f64,
-) -> Vec<f64> {
+) -> Result<Vec<f64>, TerrainError> {
+    check_heights(&heights)?;
     let alpha = 0.25;
     let mut sorted = heights
         .clone()
@@ -196,12 +233,8 @@ pub fn smooth_coasts(
         .enumerate()
         .collect::<Vec<(usize, f64)>>();
 
-    sorted.sort_unstable_by(|(_, a), (_, b)| {
-        (a - sea_level)
-            .abs()
-            .partial_cmp(&(b - sea_level).abs())
-            .unwrap()
-    });
+    sorted
+        .sort_unstable_by(|(_, a), (_, b)| (a - sea_level).abs().total_cmp(&(b - sea_level).abs()));
 
     for &(i, height) in sorted.iter() {
         if (height - sea_level).abs() > 0.015 {
@@ -219,5 +252,5 @@ pub fn smooth_coasts(
         }
     }
 
-    heights
+    Ok(heights)
 }
@@ -0,0 +1,107 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+use super::contours;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TerrainError {
+    Cancelled,
+    InvalidRadius(f64),
+    InvalidDomain {
+        width: f64,
+        height: f64,
+        scale: f64,
+    },
+    Triangulation,
+    CoincidentPoint(usize),
+    NanHeight(usize),
+    InvalidRaster {
+        width: usize,
+        height: usize,
+        len: usize,
+    },
+    Serialization(String),
+    InvalidNoiseGraph(String),
+    InvalidMask(String),
+    InvalidHeightmap(String),
+    InvalidBathymetry(String),
+    InvalidContours {
+        base: f64,
+        interval: f64,
+    },
+    InvalidGlaciation(String),
+    // A stage ran before the stage that builds what it needs, named here.
+    MissingStage(&'static str),
+}
+
+impl fmt::Display for TerrainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerrainError::Cancelled => write!(f, "terrain generation was cancelled"),
+            TerrainError::InvalidRadius(radius) => write!(
+                f,
+                "point radius must be greater than 0 and at most √2 times the shortest side of the map, got {}",
+                radius
+            ),
+            TerrainError::InvalidDomain {
+                width,
+                height,
+                scale,
+            } => write!(
+                f,
+                "map width, height and scale must be positive, got {}, {} and {}",
+                width, height, scale
+            ),
+            TerrainError::Triangulation => write!(
+                f,
+                "could not triangulate points: need at least three points not all on one line"
+            ),
+            TerrainError::CoincidentPoint(i) => {
+                write!(f, "point {} coincides with another point", i)
+            }
+            TerrainError::NanHeight(i) => write!(f, "height at index {} is NaN", i),
+            TerrainError::InvalidRaster { width, height, len } => match width.checked_mul(*height) {
+                Some(size) => write!(
+                    f,
+                    "raster of {}×{} needs {} values, got {}",
+                    width, height, size, len
+                ),
+                None => write!(f, "raster of {}×{} is too big, got {} values", width, height, len),
+            },
+            TerrainError::Serialization(message) => {
+                write!(f, "could not serialize to a JS value: {}", message)
+            }
+            TerrainError::InvalidNoiseGraph(message) => {
+                write!(f, "invalid noise graph: {}", message)
+            }
+            TerrainError::InvalidMask(message) => write!(f, "invalid mask: {}", message),
+            TerrainError::InvalidHeightmap(message) => {
+                write!(f, "could not read heightmap: {}", message)
+            }
+            TerrainError::InvalidBathymetry(message) => {
+                write!(f, "invalid bathymetry: {}", message)
+            }
+            TerrainError::InvalidContours { base, interval } => write!(
+                f,
+                "contour interval must be positive, at most {} levels apart, and base finite, got {} and {}",
+                contours::MAX_LEVELS,
+                interval,
+                base
+            ),
+            TerrainError::InvalidGlaciation(message) => {
+                write!(f, "invalid glaciation: {}", message)
+            }
+            TerrainError::MissingStage(stage) => {
+                write!(f, "a {} stage must run before this stage", stage)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TerrainError {}
+
+impl From<TerrainError> for JsValue {
+    fn from(error: TerrainError) -> JsValue {
+        js_sys::Error::new(&error.to_string()).into()
+    }
+}
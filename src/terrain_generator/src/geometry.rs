@@ -0,0 +1,211 @@
+use delaunator::EMPTY;
+use std::collections::HashMap;
+
+use super::sphere::{self, Sphere};
+use super::voronoi::{next_halfedge, Voronoi};
+
+// Size and shape of every voronoi cell, and how long the edge between each pair of neighbouring
+// cells is. Flat maps measure the cells as cut to the map by `Voronoi::clip`, in world units.
+// Planets measure them on the unit sphere, so the areas add up to 4π.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Geometry {
+    pub areas: Vec<f64>,
+    // `[x, y]` per cell. For a planet, where the centroid on the sphere lands on the map.
+    pub centroids: Vec<f64>,
+    pub perimeters: Vec<f64>,
+    // Per cell, one length per neighbour in the same order as `Delaunay::neighbors`.
+    #[serde(rename = "edgeLengths")]
+    pub edge_lengths: Vec<Vec<f64>>,
+}
+
+impl Geometry {
+    pub fn new(voronoi: &Voronoi, bounds: [f64; 4], wrap: bool) -> Geometry {
+        let points = &voronoi.delaunay.points;
+        let [xmin, _, xmax, ymax] = bounds;
+        let width = xmax - xmin;
+
+        let mut geometry = Geometry::default();
+        for (i, polygon) in voronoi.cell_polygons.iter().enumerate() {
+            // Cells entirely off the map keep their site as the centroid.
+            let (mut x, y) = centroid(polygon).unwrap_or((points[i * 2], points[i * 2 + 1]));
+            if wrap {
+                x = xmin + (x - xmin).rem_euclid(width);
+            }
+            geometry.areas.push(area(polygon));
+            geometry.centroids.extend([x, y].iter());
+            geometry.perimeters.push(perimeter(polygon));
+        }
+
+        // Same as in `Voronoi::clip`.
+        let far = 1e6 * (width + ymax - bounds[1]);
+        let center = |t: usize| {
+            (
+                voronoi.circumcenters[t * 2],
+                voronoi.circumcenters[t * 2 + 1],
+            )
+        };
+        geometry.edge_lengths = edge_lengths(voronoi, |e, twin| {
+            let p = center(e / 3);
+            let (mut qx, qy) = if twin == EMPTY {
+                let (nx, ny) = voronoi.hull_normal(e, if wrap { Some(width) } else { None });
+                (p.0 + nx * far, p.1 + ny * far)
+            } else {
+                center(twin / 3)
+            };
+            if wrap {
+                qx -= ((qx - p.0) / width).round() * width;
+            }
+            clip_segment(p, (qx, qy), bounds, wrap)
+        });
+        geometry
+    }
+
+    pub fn spherical(voronoi: &Voronoi, sphere: &Sphere) -> Geometry {
+        let position = |p: usize| &sphere.positions[p * 3..p * 3 + 3];
+        let center = |t: usize| &sphere.circumcenters[t * 3..t * 3 + 3];
+
+        let mut geometry = Geometry::default();
+        for (i, corners) in voronoi.voronoi_points.iter().enumerate() {
+            let site = position(i);
+            let (mut area, mut perimeter, mut sum) = (0., 0., [0.; 3]);
+            // Fan the cell out into spherical triangles around its site.
+            for k in 0..corners.len() {
+                let (b, c) = (center(corners[k]), center(corners[(k + 1) % corners.len()]));
+                let triangle = spherical_area(site, b, c);
+                for axis in 0..3 {
+                    sum[axis] += triangle * (site[axis] + b[axis] + c[axis]);
+                }
+                area += triangle;
+                perimeter += sphere::arc(b, c);
+            }
+            let (x, y) = if area > 0. {
+                sphere::map_position(&sphere::normalize(sum))
+            } else {
+                sphere::map_position(site)
+            };
+            geometry.areas.push(area);
+            geometry.centroids.extend([x, y].iter());
+            geometry.perimeters.push(perimeter);
+        }
+
+        geometry.edge_lengths = edge_lengths(voronoi, |e, twin| {
+            if twin == EMPTY {
+                0.
+            } else {
+                sphere::arc(center(e / 3), center(twin / 3))
+            }
+        });
+        geometry
+    }
+
+    // Length of the edge between the cells of neighbouring points `i` and `j`, `None` if they
+    // aren't neighbours.
+    pub fn edge_length(&self, voronoi: &Voronoi, i: usize, j: usize) -> Option<f64> {
+        let k = voronoi.delaunay.neighbors[i].iter().position(|&n| n == j)?;
+        Some(self.edge_lengths[i][k])
+    }
+}
+
+// Measures each delaunay edge once with `length(e, halfedges[e])` and lines the lengths up with
+// `Delaunay::neighbors`.
+fn edge_lengths<F: Fn(usize, usize) -> f64>(voronoi: &Voronoi, length: F) -> Vec<Vec<f64>> {
+    let delaunay = &voronoi.delaunay;
+    let key = |a: usize, b: usize| (a.min(b), a.max(b));
+
+    let mut lengths = HashMap::new();
+    for (e, &twin) in delaunay.halfedges.iter().enumerate() {
+        if twin != EMPTY && twin < e {
+            continue;
+        }
+        let (a, b) = (delaunay.triangles[e], delaunay.triangles[next_halfedge(e)]);
+        lengths.insert(key(a, b), length(e, twin));
+    }
+
+    delaunay
+        .neighbors
+        .iter()
+        .enumerate()
+        .map(|(i, neighbors)| {
+            neighbors
+                .iter()
+                .map(|&j| lengths.get(&key(i, j)).copied().unwrap_or(0.))
+                .collect()
+        })
+        .collect()
+}
+
+// Flat polygons, `[x0, y0, x1, y1, ...]`, closed between the last corner and the first.
+pub fn area(polygon: &[f64]) -> f64 {
+    signed_area(polygon).abs()
+}
+
+pub fn centroid(polygon: &[f64]) -> Option<(f64, f64)> {
+    let area = signed_area(polygon);
+    if area == 0. || !area.is_finite() {
+        return None;
+    }
+    let (mut x, mut y) = (0., 0.);
+    for_each_side(polygon, |(x0, y0), (x1, y1)| {
+        let cross = x0 * y1 - x1 * y0;
+        x += (x0 + x1) * cross;
+        y += (y0 + y1) * cross;
+    });
+    Some((x / (6. * area), y / (6. * area)))
+}
+
+pub fn perimeter(polygon: &[f64]) -> f64 {
+    let mut perimeter = 0.;
+    for_each_side(polygon, |(x0, y0), (x1, y1)| {
+        perimeter += libm::hypot(x1 - x0, y1 - y0)
+    });
+    perimeter
+}
+
+fn signed_area(polygon: &[f64]) -> f64 {
+    let mut area = 0.;
+    for_each_side(polygon, |(x0, y0), (x1, y1)| area += x0 * y1 - x1 * y0);
+    area / 2.
+}
+
+fn for_each_side<F: FnMut((f64, f64), (f64, f64))>(polygon: &[f64], mut f: F) {
+    let n = polygon.len() / 2;
+    for k in 0..n {
+        let l = (k + 1) % n;
+        f(
+            (polygon[k * 2], polygon[k * 2 + 1]),
+            (polygon[l * 2], polygon[l * 2 + 1]),
+        );
+    }
+}
+
+// Liang–Barsky, the length of the part of `p`–`q` inside `bounds`. Only the top and bottom count
+// when `wrap` is set.
+fn clip_segment(p: (f64, f64), q: (f64, f64), bounds: [f64; 4], wrap: bool) -> f64 {
+    let [xmin, ymin, xmax, ymax] = bounds;
+    let (dx, dy) = (q.0 - p.0, q.1 - p.1);
+    let mut sides = vec![(-dy, p.1 - ymin), (dy, ymax - p.1)];
+    if !wrap {
+        sides.extend([(-dx, p.0 - xmin), (dx, xmax - p.0)].iter());
+    }
+
+    let (mut enter, mut exit) = (0_f64, 1_f64);
+    for &(towards, room) in sides.iter() {
+        if towards == 0. {
+            if room < 0. {
+                return 0.;
+            }
+        } else if towards < 0. {
+            enter = enter.max(room / towards);
+        } else {
+            exit = exit.min(room / towards);
+        }
+    }
+    (exit - enter).max(0.) * libm::hypot(dx, dy)
+}
+
+// Van Oosterom and Strackee's formula for the solid angle of a triangle on the unit sphere.
+fn spherical_area(a: &[f64], b: &[f64], c: &[f64]) -> f64 {
+    let numerator = sphere::dot(a, &sphere::cross(b, c)).abs();
+    let denominator = 1. + sphere::dot(a, b) + sphere::dot(b, c) + sphere::dot(c, a);
+    2. * libm::atan2(numerator, denominator)
+}
@@ -0,0 +1,170 @@
+use super::distance::nearest_from;
+use super::erosion::{check_heights, fill_sinks};
+use super::error::TerrainError;
+use super::progress::Progress;
+
+// Ice on high and cold ground, and the valleys it carves on its way down. The snow line is
+// `snow_line` above sea level on the equator and comes down to the sea at the poles. Glaciers
+// flow downhill from there, reaching up to `reach` below the snow line before they melt, and
+// those that make it to the sea cut fjords. `north` and `south` are the latitudes of the top and
+// bottom of a flat map. Heights are in the same units as the world's heights, widths and
+// `catchment` (an area) in world units (radians on a planet).
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Glaciation {
+    pub north: f64,
+    pub south: f64,
+    pub snow_line: f64,
+    pub reach: f64,
+    // How far the biggest glaciers dig into the valley floor.
+    pub depth: f64,
+    // How wide the biggest glaciers' valleys are.
+    pub width: f64,
+    // The area of ice feeding a glacier before it grows to most of its `depth` and `width`.
+    pub catchment: f64,
+}
+
+impl Default for Glaciation {
+    fn default() -> Glaciation {
+        Glaciation {
+            north: 90.,
+            south: -90.,
+            snow_line: 0.25,
+            reach: 0.1,
+            depth: 0.08,
+            width: 0.04,
+            catchment: 0.01,
+        }
+    }
+}
+
+// Glaciers that melt on land leave their valley floors at least this far above sea level.
+const MIN_HEIGHT: f64 = 1e-3;
+
+impl Glaciation {
+    pub fn validate(&self) -> Result<(), TerrainError> {
+        if !(-90. ..=90.).contains(&self.north) || !(-90. ..=90.).contains(&self.south) {
+            return Err(TerrainError::InvalidGlaciation(format!(
+                "latitudes must be between -90 and 90, got {} and {}",
+                self.north, self.south
+            )));
+        }
+        let sizes = [self.width, self.catchment];
+        if !sizes.iter().all(|&s| s.is_finite() && s > 0.) {
+            return Err(TerrainError::InvalidGlaciation(
+                "width and catchment must be positive".to_string(),
+            ));
+        }
+        let heights = [self.snow_line, self.reach, self.depth];
+        if !heights.iter().all(|&h| h.is_finite() && h >= 0.) {
+            return Err(TerrainError::InvalidGlaciation(
+                "snow line, reach and depth must not be negative".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Height of the snow line at `latitude`, in degrees.
+    pub fn snow_line(&self, latitude: f64, sea_level: f64) -> f64 {
+        sea_level + self.snow_line * libm::cos(latitude.to_radians()).max(0.)
+    }
+
+    // Carves the corner `heights` under the ice, `adjacent` being the corners' neighbours and
+    // `area` the area around each corner. Returns which corners are under ice. Stops early if
+    // `progress` is cancelled.
+    #[allow(clippy::too_many_arguments)]
+    pub fn carve<F: Fn(usize, usize) -> f64>(
+        &self,
+        heights: &mut Vec<f64>,
+        adjacent: &Vec<Vec<usize>>,
+        latitudes: &[f64],
+        sea_level: f64,
+        area: f64,
+        length: F,
+        progress: &Progress,
+    ) -> Result<Vec<bool>, TerrainError> {
+        check_heights(heights)?;
+        let n = heights.len();
+        let snow_lines: Vec<f64> = latitudes
+            .iter()
+            .map(|&latitude| self.snow_line(latitude, sea_level))
+            .collect();
+        let downhill = |i: usize| {
+            adjacent[i]
+                .iter()
+                .copied()
+                .filter(|&j| heights[j] < heights[i])
+                .min_by(|&a, &b| heights[a].total_cmp(&heights[b]))
+        };
+
+        // Ice gathers above the snow line and flows down the steepest way, as far as it lasts or
+        // into the sea.
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| heights[b].total_cmp(&heights[a]));
+        let mut flux = vec![0.; n];
+        let mut ice = vec![false; n];
+        for &i in order.iter() {
+            let height = heights[i];
+            if height >= snow_lines[i] {
+                flux[i] += 1.;
+            }
+            if flux[i] == 0. || (height >= sea_level && height < snow_lines[i] - self.reach) {
+                continue;
+            }
+            ice[i] = true;
+            if height < sea_level {
+                continue;
+            }
+            if let Some(j) = downhill(i) {
+                flux[j] += flux[i];
+            }
+        }
+
+        progress.check()?;
+
+        // Lowest first, so each glacier knows whether it ends in the sea before the ice above it.
+        let mut to_sea = vec![false; n];
+        for &i in order.iter().rev().filter(|&&i| ice[i]) {
+            to_sea[i] = heights[i] < sea_level || downhill(i).is_some_and(|j| to_sea[j]);
+        }
+
+        // Valley floors only ever go down along the flow, so the ice doesn't leave basins behind.
+        let saturation = |i: usize| 1. - libm::exp(-flux[i] * area / self.catchment);
+        let mut floors = vec![f64::INFINITY; n];
+        for &i in order.iter().filter(|&&i| ice[i]) {
+            let mut floor = (heights[i] - self.depth * saturation(i)).min(floors[i]);
+            if !to_sea[i] {
+                floor = floor.max(sea_level + MIN_HEIGHT);
+            }
+            floors[i] = floor.min(heights[i]);
+            if heights[i] >= sea_level {
+                if let Some(j) = downhill(i) {
+                    floors[j] = floors[j].min(floors[i]);
+                }
+            }
+        }
+
+        // A flat floor with steep walls across the valley: U-shaped rather than V-shaped. Each
+        // corner takes its shape from the nearest glacier.
+        let width = |i: usize| self.width * saturation(i).sqrt();
+        let glaciers: Vec<(usize, f64)> = (0..n)
+            .filter(|&i| ice[i] && floors[i] < heights[i])
+            .map(|i| (i, 0.))
+            .collect();
+        let mut carved = heights.clone();
+        let nearest = nearest_from(adjacent, &glaciers, self.width, length, progress)?;
+        for (j, nearest) in nearest.into_iter().enumerate() {
+            if let Some((i, distance)) = nearest {
+                let (floor, width) = (floors[i], width(i));
+                if distance < width && heights[j] > floor {
+                    let wall = (distance / width).powi(4);
+                    carved[j] = floor + (heights[j] - floor) * wall;
+                }
+            }
+        }
+
+        // Whatever basins are left fill up like lakes, so rivers still find their way out.
+        *heights = fill_sinks(carved, adjacent, sea_level, &[])?;
+        Ok(ice)
+    }
+}
@@ -0,0 +1,24 @@
+use super::raster::Raster;
+use super::terrain_generator::TerrainGenerator;
+
+// A painted heightmap stretched over the domain, 0 at the sea floor and 1 at the peaks, used in
+// place of the noise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    pub raster: Raster,
+    // How much of the noise to keep, from 0 (the heightmap alone) to 1 (the noise alone).
+    pub noise: f64,
+}
+
+// Blends the generator's heightmap, if it has one, into the noise `heights` at each `[x, y]` of
+// `points`.
+pub fn apply_heightmap(points: &[f64], mut heights: Vec<f64>, gen: &TerrainGenerator) -> Vec<f64> {
+    if let Some(Heightmap { raster, noise }) = &gen.heightmap {
+        let noise = noise.clamp(0., 1.);
+        for (height, p) in heights.iter_mut().zip(points.chunks_exact(2)) {
+            let (u, v) = gen.domain.normalize(p[0], p[1]);
+            *height = raster.sample(u, v) * (1. - noise) + *height * noise;
+        }
+    }
+    heights
+}
@@ -7,11 +7,32 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 #[macro_use]
 extern crate serde_derive;
 
+pub mod bathymetry;
+pub mod circulation;
 mod coasts;
+pub mod contours;
+pub mod density;
+pub mod derivatives;
+pub mod distance;
+pub mod domain;
 mod erosion;
-mod noise;
+pub mod error;
+pub mod geometry;
+pub mod glaciation;
+pub mod heightmap;
+pub mod masks;
+pub mod noise;
+pub mod pipeline;
 mod poisson;
+pub mod progress;
+pub mod raster;
+mod relax;
 mod rivers;
+pub mod seed;
+pub mod spatial;
+pub mod sphere;
+pub mod stage;
 pub mod terrain_generator;
+pub mod tile;
 mod utils;
-mod voronoi;
+pub mod voronoi;
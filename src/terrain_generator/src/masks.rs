@@ -0,0 +1,173 @@
+use super::error::TerrainError;
+use super::raster::Raster;
+use super::terrain_generator::TerrainGenerator;
+
+// Where land may appear, from 0 (only sea) to 1 (anything goes). Positions and sizes are in world
+// units, over the generator's domain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Mask {
+    // An island around (`x`, `y`), fading out to sea by `radius` away.
+    Radial {
+        x: f64,
+        y: f64,
+        radius: f64,
+    },
+    // `count` islands like `Radial`, scattered over the map by the generator's seed.
+    Continents {
+        count: usize,
+        radius: f64,
+    },
+    // Sea all along the edges of the map, fading in over `width`. Only the top and bottom edges
+    // when the map wraps.
+    OceanBorder {
+        width: f64,
+    },
+    // Land inside the polygon, `[x, y]` corners in order, fading out to sea over `falloff` outside.
+    Polygon {
+        points: Vec<[f64; 2]>,
+        #[serde(default)]
+        falloff: f64,
+    },
+    // A grayscale raster stretched over the map.
+    #[serde(skip)]
+    Image(Raster),
+}
+
+// A mask and how much it counts, from 0 (not at all) to 1 (heights are multiplied by it).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaskLayer {
+    pub mask: Mask,
+    pub strength: f64,
+}
+
+impl Mask {
+    pub fn validate(&self) -> Result<(), TerrainError> {
+        let size = match self {
+            Mask::Radial { radius, .. } | Mask::Continents { radius, .. } => *radius,
+            Mask::OceanBorder { width } => *width,
+            Mask::Polygon { points, .. } if points.len() < 3 => {
+                return Err(TerrainError::InvalidMask(
+                    "a polygon needs at least 3 points".to_string(),
+                ))
+            }
+            Mask::Polygon { .. } | Mask::Image(_) => 1.,
+        };
+        if size.is_finite() && size > 0. {
+            Ok(())
+        } else {
+            Err(TerrainError::InvalidMask(format!(
+                "radius and width must be positive, got {}",
+                size
+            )))
+        }
+    }
+
+    // The mask at each `[x, y]` of `points`.
+    pub fn values(&self, points: &[f64], gen: &TerrainGenerator) -> Vec<f64> {
+        let domain = &gen.domain;
+        let island = |(cx, cy): (f64, f64), radius: f64, x: f64, y: f64| {
+            smoothstep(1. - domain.distance((cx, cy), (x, y)) / radius)
+        };
+        let each = |f: &dyn Fn(f64, f64) -> f64| -> Vec<f64> {
+            points.chunks_exact(2).map(|p| f(p[0], p[1])).collect()
+        };
+
+        match self {
+            Mask::Radial { x, y, radius } => each(&|px, py| island((*x, *y), *radius, px, py)),
+            Mask::Continents { count, radius } => {
+                let mut rng = gen.stage_rng("continents");
+                let [xmin, ymin, xmax, ymax] = domain.bounds();
+                // Keep the middle of each continent off the edge where there is room.
+                let inset = |min: f64, max: f64| (radius / 2.).min((max - min) / 2.);
+                let (ix, iy) = (inset(xmin, xmax), inset(ymin, ymax));
+                let centers: Vec<(f64, f64)> = (0..*count)
+                    .map(|_| {
+                        let x = xmin + ix + rng.rand::<f64>() * (xmax - xmin - 2. * ix);
+                        let y = ymin + iy + rng.rand::<f64>() * (ymax - ymin - 2. * iy);
+                        (x, y)
+                    })
+                    .collect();
+                each(&|x, y| {
+                    centers
+                        .iter()
+                        .map(|&center| island(center, *radius, x, y))
+                        .fold(0., f64::max)
+                })
+            }
+            Mask::OceanBorder { width } => {
+                let [xmin, ymin, xmax, ymax] = domain.bounds();
+                each(&|x, y| {
+                    let mut edge = (y - ymin).min(ymax - y);
+                    if !domain.wrap {
+                        edge = edge.min(x - xmin).min(xmax - x);
+                    }
+                    smoothstep(edge / width)
+                })
+            }
+            Mask::Polygon {
+                points: corners,
+                falloff,
+            } => each(&|x, y| {
+                if inside(corners, x, y) {
+                    1.
+                } else if *falloff > 0. {
+                    smoothstep(1. - distance_to_outline(corners, x, y) / falloff)
+                } else {
+                    0.
+                }
+            }),
+            Mask::Image(raster) => each(&|x, y| {
+                let (u, v) = domain.normalize(x, y);
+                raster.sample(u, v).clamp(0., 1.)
+            }),
+        }
+    }
+}
+
+// Pulls heights down towards 0 wherever the generator's masks keep land out, each by its
+// strength.
+pub fn apply_masks(points: &[f64], mut heights: Vec<f64>, gen: &TerrainGenerator) -> Vec<f64> {
+    for layer in gen.masks.iter() {
+        let strength = layer.strength.clamp(0., 1.);
+        for (height, mask) in heights.iter_mut().zip(layer.mask.values(points, gen)) {
+            *height *= 1. - strength * (1. - mask);
+        }
+    }
+    heights
+}
+
+pub(crate) fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+// Even-odd rule.
+fn inside(corners: &[[f64; 2]], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    for k in 0..corners.len() {
+        let [x0, y0] = corners[k];
+        let [x1, y1] = corners[(k + 1) % corners.len()];
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) / (y1 - y0) * (x1 - x0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn distance_to_outline(corners: &[[f64; 2]], x: f64, y: f64) -> f64 {
+    let mut nearest = f64::INFINITY;
+    for k in 0..corners.len() {
+        let [x0, y0] = corners[k];
+        let [x1, y1] = corners[(k + 1) % corners.len()];
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length = dx * dx + dy * dy;
+        let t = if length > 0. {
+            (((x - x0) * dx + (y - y0) * dy) / length).clamp(0., 1.)
+        } else {
+            0.
+        };
+        nearest = nearest.min(libm::hypot(x0 + t * dx - x, y0 + t * dy - y));
+    }
+    nearest
+}
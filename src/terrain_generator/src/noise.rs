@@ -1,73 +1,689 @@
 use bracket_noise::prelude::*;
-use bracket_random::prelude::*;
+use std::f64::consts::PI;
+use std::rc::Rc;
 
+use super::error::TerrainError;
+use super::seed::derive_seed;
+
+// How the height noise is put together, as plain data so terrain styles can be written as JSON.
+// Sources give values of roughly -1 to 1, and the rest keep to that range where they can.
+// `NoiseNode::default()` is the warped simplex every map used before graphs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum NoiseNode {
+    Simplex(Source),
+    Perlin(Source),
+    Value(Source),
+    Cellular {
+        #[serde(flatten)]
+        source: Source,
+        #[serde(default)]
+        output: CellularOutput,
+    },
+    Constant {
+        value: f64,
+    },
+    // Sharp, branching ridgelines. Each octave is weighted by the one before it, so detail
+    // gathers along the ridges and the valleys between stay smooth.
+    RidgedMultifractal {
+        #[serde(flatten)]
+        source: Source,
+        // Height of the ridges before squaring, around 1.
+        #[serde(default = "default_offset")]
+        offset: f64,
+        // How strongly each octave is held to the ridges of the last.
+        #[serde(default = "default_sharpness")]
+        sharpness: f64,
+    },
+    // Shapes made from the distances to scattered points, see `WorleyOutput`.
+    Worley {
+        #[serde(flatten)]
+        source: Source,
+        #[serde(default)]
+        output: WorleyOutput,
+        // How far the points stray from the middle of their grid cells, 0 to 1.
+        #[serde(default = "default_jitter")]
+        jitter: f64,
+    },
+
+    // Sharp crests where `source` crosses 0.
+    Ridged {
+        source: Box<NoiseNode>,
+    },
+    // Sharp troughs where `source` crosses 0.
+    Billow {
+        source: Box<NoiseNode>,
+    },
+    Abs {
+        source: Box<NoiseNode>,
+    },
+    // `steps` flat levels between -1 and 1, with steep rises between them.
+    Terrace {
+        source: Box<NoiseNode>,
+        steps: usize,
+    },
+    Clamp {
+        source: Box<NoiseNode>,
+        min: f64,
+        max: f64,
+    },
+    // Straight lines between `[input, output]` points, flat past the first and last.
+    Curve {
+        source: Box<NoiseNode>,
+        points: Vec<[f64; 2]>,
+    },
+    ScaleBias {
+        source: Box<NoiseNode>,
+        scale: f64,
+        bias: f64,
+    },
+
+    Add {
+        sources: Vec<NoiseNode>,
+    },
+    Multiply {
+        sources: Vec<NoiseNode>,
+    },
+    // All `a` where `factor` is -1, all `b` where it is 1.
+    Blend {
+        a: Box<NoiseNode>,
+        b: Box<NoiseNode>,
+        factor: Box<NoiseNode>,
+    },
+    // `a` where `mask` is below `threshold`, `b` above, fading over `falloff` either side.
+    Select {
+        a: Box<NoiseNode>,
+        b: Box<NoiseNode>,
+        mask: Box<NoiseNode>,
+        threshold: f64,
+        #[serde(default)]
+        falloff: f64,
+    },
+
+    // `base`, plus `amount` times `source` where `mask` (or `base` itself, without a mask) is
+    // above `low`, fading in fully by `high`. For mountains only on high ground, say.
+    Mix {
+        base: Box<NoiseNode>,
+        source: Box<NoiseNode>,
+        #[serde(default)]
+        mask: Option<Box<NoiseNode>>,
+        low: f64,
+        high: f64,
+        #[serde(default = "default_amount")]
+        amount: f64,
+    },
+
+    // `source` at `factor` times the position, so features shrink by `factor`.
+    Scale {
+        source: Box<NoiseNode>,
+        factor: f64,
+    },
+    // `source`, pushed `length * strength` in the direction of `angle` (in radians).
+    Warp {
+        source: Box<NoiseNode>,
+        angle: Box<NoiseNode>,
+        length: Box<NoiseNode>,
+        strength: f64,
+    },
+}
+
+// Settings shared by the sources. More than one octave makes it fractal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Source {
+    // Each source's seed comes from the generator's seed and this name, so sources with the same
+    // name and settings give the same noise.
+    pub seed: String,
+    pub frequency: f64,
+    pub octaves: usize,
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+impl Default for Source {
+    fn default() -> Source {
+        Source {
+            seed: "noise".to_string(),
+            frequency: 1.,
+            octaves: 1,
+            lacunarity: 2.,
+            gain: 0.5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub enum CellularOutput {
+    // A random value per cell.
+    CellValue,
+    // Distance to the nearest cell point.
+    #[default]
+    Distance,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum WorleyOutput {
+    // Distance to the nearest point: round pits.
+    #[default]
+    F1,
+    // How much further the second nearest point is: ridges along the cell edges.
+    F2MinusF1,
+    // A bowl around each point with a raised rim, fading out into the flat.
+    Crater,
+    // Flat tops with steep sides down to the cell edges.
+    Mesa,
+}
+
+fn default_offset() -> f64 {
+    1.
+}
+
+fn default_sharpness() -> f64 {
+    2.
+}
+
+fn default_jitter() -> f64 {
+    1.
+}
+
+fn default_amount() -> f64 {
+    1.
+}
+
+impl Default for NoiseNode {
+    fn default() -> NoiseNode {
+        let source = |seed: &str, frequency, octaves, lacunarity| Source {
+            seed: seed.to_string(),
+            frequency,
+            octaves,
+            lacunarity,
+            gain: 0.5,
+        };
+        let force = 0.25; // magic
+        let wavyness = 5e-1; // magic
+        NoiseNode::Warp {
+            source: Box::new(NoiseNode::Simplex(source("height", 0.8, 5, 3.))),
+            angle: Box::new(NoiseNode::Scale {
+                source: Box::new(NoiseNode::Simplex(source("theta", 2., 1, 2.))),
+                factor: force,
+            }),
+            length: Box::new(NoiseNode::Scale {
+                source: Box::new(NoiseNode::Simplex(source("offset", 2., 1, 2.))),
+                factor: force,
+            }),
+            strength: wavyness,
+        }
+    }
+}
+
+// `NoiseNode` with its sources seeded and set up, ready to sample.
+enum Compiled {
+    Source(FastNoise),
+    Constant(f64),
+    RidgedMultifractal(FastNoise, Ridges),
+    Worley(Worley),
+    Ridged(Box<Compiled>),
+    Billow(Box<Compiled>),
+    Abs(Box<Compiled>),
+    Terrace(Box<Compiled>, usize),
+    Clamp(Box<Compiled>, f64, f64),
+    Curve(Box<Compiled>, Vec<[f64; 2]>),
+    ScaleBias(Box<Compiled>, f64, f64),
+    Add(Vec<Compiled>),
+    Multiply(Vec<Compiled>),
+    Blend(Box<[Compiled; 3]>),
+    Select(Box<[Compiled; 3]>, f64, f64),
+    Scale(Box<Compiled>, f64),
+    Warp(Box<[Compiled; 3]>, f64),
+    Mix(Box<[Compiled; 2]>, Option<Box<Compiled>>, [f64; 3]),
+}
+
+struct Ridges {
+    octaves: usize,
+    lacunarity: f64,
+    gain: f64,
+    offset: f64,
+    sharpness: f64,
+}
+
+struct Worley {
+    seed: u64,
+    frequency: f64,
+    output: WorleyOutput,
+    jitter: f64,
+}
+
+// Where noise is sampled. `Cylinder` bends the plane around so it repeats every `period` along x.
+#[derive(Clone, Copy)]
+enum Space {
+    Flat,
+    Cylinder(f64),
+    Sphere,
+}
+
+// Compiled once, clones share it.
+#[derive(Clone)]
 pub struct Noise {
-    height: FastNoise,
-    theta: FastNoise,
-    offset: FastNoise,
-    // noise_resources: FastNoise,
-    uniform: RandomNumberGenerator,
+    root: Rc<Compiled>,
 }
 
 impl Noise {
     pub fn new(seed: u64) -> Noise {
-        let mut height = FastNoise::seeded(seed);
-        height.set_noise_type(NoiseType::SimplexFractal);
-        height.set_fractal_type(FractalType::FBM);
-        height.set_fractal_octaves(5);
-        height.set_fractal_gain(0.5);
-        height.set_fractal_lacunarity(3.0);
-        height.set_frequency(0.8);
-
-        let mut theta = FastNoise::seeded(seed + 1);
-        theta.set_noise_type(NoiseType::Simplex);
-        theta.set_frequency(2.0);
-
-        let mut offset = FastNoise::seeded(seed + 2);
-        offset.set_noise_type(NoiseType::Simplex);
-        offset.set_frequency(2.0);
-
-        // let mut noise_resources = FastNoise::seeded(seed + 3);
-        // noise_resources.set_noise_type(NoiseType::Simplex);
-        // noise_resources.set_frequency(2.0);
-
-        let uniform = RandomNumberGenerator::seeded(seed);
-
-        Noise {
-            height,
-            theta,
-            offset,
-            // noise_resources,
-            uniform,
+        Noise::from_graph(seed, &NoiseNode::default()).expect("the default graph is valid")
+    }
+
+    pub fn from_graph(seed: u64, graph: &NoiseNode) -> Result<Noise, TerrainError> {
+        Ok(Noise {
+            root: Rc::new(Noise::compile(seed, graph)?),
+        })
+    }
+
+    fn compile(seed: u64, node: &NoiseNode) -> Result<Compiled, TerrainError> {
+        let invalid = |message: &str| TerrainError::InvalidNoiseGraph(message.to_string());
+        let boxed = |node: &NoiseNode| Noise::compile(seed, node).map(Box::new);
+        let three = |a: &NoiseNode, b: &NoiseNode, c: &NoiseNode| {
+            Ok::<_, TerrainError>(Box::new([
+                Noise::compile(seed, a)?,
+                Noise::compile(seed, b)?,
+                Noise::compile(seed, c)?,
+            ]))
+        };
+        let all = |nodes: &[NoiseNode]| {
+            if nodes.is_empty() {
+                return Err(invalid("`add` and `multiply` need at least one source"));
+            }
+            nodes
+                .iter()
+                .map(|node| Noise::compile(seed, node))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(match node {
+            NoiseNode::Simplex(source) => Compiled::Source(source.build(seed, NoiseKind::Simplex)?),
+            NoiseNode::Perlin(source) => Compiled::Source(source.build(seed, NoiseKind::Perlin)?),
+            NoiseNode::Value(source) => Compiled::Source(source.build(seed, NoiseKind::Value)?),
+            NoiseNode::Cellular { source, output } => {
+                Compiled::Source(source.build(seed, NoiseKind::Cellular(*output))?)
+            }
+            NoiseNode::Constant { value } => Compiled::Constant(*value),
+            NoiseNode::RidgedMultifractal {
+                source,
+                offset,
+                sharpness,
+            } => {
+                let single = Source {
+                    octaves: 1,
+                    ..source.clone()
+                };
+                let ridges = Ridges {
+                    octaves: source.octaves,
+                    lacunarity: source.lacunarity,
+                    gain: source.gain,
+                    offset: *offset,
+                    sharpness: *sharpness,
+                };
+                source.validate()?;
+                Compiled::RidgedMultifractal(single.build(seed, NoiseKind::Simplex)?, ridges)
+            }
+            NoiseNode::Worley {
+                source,
+                output,
+                jitter,
+            } => {
+                source.validate()?;
+                Compiled::Worley(Worley {
+                    seed: derive_seed(seed, &source.seed),
+                    frequency: source.frequency,
+                    output: *output,
+                    jitter: jitter.clamp(0., 1.),
+                })
+            }
+            NoiseNode::Ridged { source } => Compiled::Ridged(boxed(source)?),
+            NoiseNode::Billow { source } => Compiled::Billow(boxed(source)?),
+            NoiseNode::Abs { source } => Compiled::Abs(boxed(source)?),
+            NoiseNode::Terrace { source, steps } => {
+                if *steps < 2 {
+                    return Err(invalid("`terrace` needs at least 2 steps"));
+                }
+                Compiled::Terrace(boxed(source)?, *steps)
+            }
+            NoiseNode::Clamp { source, min, max } => Compiled::Clamp(boxed(source)?, *min, *max),
+            NoiseNode::Curve { source, points } => {
+                if points.len() < 2 {
+                    return Err(invalid("`curve` needs at least 2 points"));
+                }
+                let mut points = points.clone();
+                points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+                Compiled::Curve(boxed(source)?, points)
+            }
+            NoiseNode::ScaleBias {
+                source,
+                scale,
+                bias,
+            } => Compiled::ScaleBias(boxed(source)?, *scale, *bias),
+            NoiseNode::Add { sources } => Compiled::Add(all(sources)?),
+            NoiseNode::Multiply { sources } => Compiled::Multiply(all(sources)?),
+            NoiseNode::Blend { a, b, factor } => Compiled::Blend(three(a, b, factor)?),
+            NoiseNode::Select {
+                a,
+                b,
+                mask,
+                threshold,
+                falloff,
+            } => Compiled::Select(three(a, b, mask)?, *threshold, falloff.max(0.)),
+            NoiseNode::Scale { source, factor } => Compiled::Scale(boxed(source)?, *factor),
+            NoiseNode::Warp {
+                source,
+                angle,
+                length,
+                strength,
+            } => Compiled::Warp(three(source, angle, length)?, *strength),
+            NoiseNode::Mix {
+                base,
+                source,
+                mask,
+                low,
+                high,
+                amount,
+            } => Compiled::Mix(
+                Box::new([Noise::compile(seed, base)?, Noise::compile(seed, source)?]),
+                match mask {
+                    Some(mask) => Some(boxed(mask)?),
+                    None => None,
+                },
+                [*low, *high, *amount],
+            ),
+        })
+    }
+
+    // Bend the plane into a cylinder of circumference `period` and sample 3D noise on its surface,
+    // so that x = 0 and x = `period` give the same value.
+    fn cylinder(x: f64, y: f64, period: f64) -> (f64, f64, f64) {
+        let radius = period / (2. * PI);
+        let angle = x / radius;
+        (libm::cos(angle) * radius, libm::sin(angle) * radius, y)
+    }
+
+    // Where in the noise's own 3D space (or 2D, when flat) the position is.
+    fn position(space: Space, x: f64, y: f64, z: f64) -> [f64; 3] {
+        match space {
+            Space::Flat => [x, y, 0.],
+            Space::Cylinder(period) => {
+                let (cx, cy, cz) = Noise::cylinder(x, y, period);
+                [cx, cy, cz]
+            }
+            Space::Sphere => [x, y, z],
+        }
+    }
+
+    fn source(noise: &FastNoise, space: Space, x: f64, y: f64, z: f64) -> f64 {
+        let [x, y, z] = Noise::position(space, x, y, z);
+        match space {
+            Space::Flat => noise.get_noise(x as f32, y as f32) as f64,
+            _ => noise.get_noise3d(x as f32, y as f32, z as f32) as f64,
         }
     }
 
-    pub fn height(&self, x: f64, y: f64) -> f64 {
-        self.height.get_noise(x as f32, y as f32) as f64
+    fn scaled(space: Space, factor: f64) -> Space {
+        match space {
+            Space::Cylinder(period) => Space::Cylinder(period * factor),
+            space => space,
+        }
+    }
+
+    // Musgrave's ridged multifractal, scaled to -1 to 1.
+    fn ridges(noise: &FastNoise, ridges: &Ridges, space: Space, x: f64, y: f64, z: f64) -> f64 {
+        let (mut sum, mut total) = (0., 0.);
+        let (mut frequency, mut amplitude, mut weight) = (1., 1., 1.);
+        for octave in 0..ridges.octaves {
+            // Shifted along y, so octaves don't all share a ridge at the origin.
+            let shift = octave as f64 * 17.3; // magic
+            let n = Noise::source(
+                noise,
+                Noise::scaled(space, frequency),
+                x * frequency,
+                y * frequency + shift,
+                z * frequency,
+            );
+            let signal = (ridges.offset - n.abs()).powi(2) * weight;
+            weight = (signal * ridges.sharpness).clamp(0., 1.);
+
+            sum += signal * amplitude;
+            total += ridges.offset.powi(2) * amplitude;
+            frequency *= ridges.lacunarity;
+            amplitude *= ridges.gain;
+        }
+        if total > 0. {
+            sum / total * 2. - 1.
+        } else {
+            0.
+        }
     }
 
-    pub fn theta(&self, x: f64, y: f64) -> f64 {
-        self.theta.get_noise(x as f32, y as f32) as f64
+    fn sample(node: &Compiled, space: Space, x: f64, y: f64, z: f64) -> f64 {
+        let sample = |node: &Compiled| Noise::sample(node, space, x, y, z);
+        match node {
+            Compiled::Source(noise) => Noise::source(noise, space, x, y, z),
+            Compiled::Constant(value) => *value,
+            Compiled::RidgedMultifractal(noise, ridges) => {
+                Noise::ridges(noise, ridges, space, x, y, z)
+            }
+            Compiled::Worley(worley) => {
+                let f = worley.frequency;
+                let p = Noise::position(Noise::scaled(space, f), x * f, y * f, z * f);
+                let dimensions = if let Space::Flat = space { 2 } else { 3 };
+                worley.sample(p, dimensions)
+            }
+            Compiled::Ridged(source) => 1. - 2. * sample(source).abs(),
+            Compiled::Billow(source) => 2. * sample(source).abs() - 1.,
+            Compiled::Abs(source) => sample(source).abs(),
+            Compiled::Terrace(source, steps) => {
+                let level = (sample(source).clamp(-1., 1.) + 1.) / 2. * (steps - 1) as f64;
+                let step = level.floor().min((steps - 2) as f64);
+                let rise = (level - step).powi(3);
+                (step + rise) / (steps - 1) as f64 * 2. - 1.
+            }
+            Compiled::Clamp(source, min, max) => sample(source).max(*min).min(*max),
+            Compiled::Curve(source, points) => curve(points, sample(source)),
+            Compiled::ScaleBias(source, scale, bias) => sample(source) * scale + bias,
+            Compiled::Add(sources) => sources.iter().map(sample).sum(),
+            Compiled::Multiply(sources) => sources.iter().map(sample).product(),
+            Compiled::Blend(nodes) => {
+                let [a, b, factor] = &**nodes;
+                let t = ((sample(factor) + 1.) / 2.).clamp(0., 1.);
+                sample(a) * (1. - t) + sample(b) * t
+            }
+            Compiled::Select(nodes, threshold, falloff) => {
+                let [a, b, mask] = &**nodes;
+                let mask = sample(mask);
+                if mask <= threshold - falloff {
+                    sample(a)
+                } else if mask >= threshold + falloff {
+                    sample(b)
+                } else {
+                    let t = (mask - (threshold - falloff)) / (2. * falloff);
+                    let t = t * t * (3. - 2. * t);
+                    sample(a) * (1. - t) + sample(b) * t
+                }
+            }
+            Compiled::Scale(source, factor) => Noise::sample(
+                source,
+                Noise::scaled(space, *factor),
+                x * factor,
+                y * factor,
+                z * factor,
+            ),
+            Compiled::Warp(nodes, strength) => {
+                let [source, angle, length] = &**nodes;
+                let (angle, length) = (sample(angle), sample(length));
+                let x = x + libm::cos(angle) * length * strength;
+                let y = y + libm::sin(angle) * length * strength;
+                Noise::sample(source, space, x, y, z)
+            }
+            Compiled::Mix(nodes, mask, [low, high, amount]) => {
+                let [base, source] = &**nodes;
+                let base = sample(base);
+                let by = match mask {
+                    Some(mask) => sample(mask),
+                    None => base,
+                };
+                let t = if high > low {
+                    ((by - low) / (high - low)).clamp(0., 1.)
+                } else if by >= *low {
+                    1.
+                } else {
+                    0.
+                };
+                let t = t * t * (3. - 2. * t);
+                if t > 0. {
+                    base + amount * t * sample(source)
+                } else {
+                    base
+                }
+            }
+        }
     }
 
-    pub fn offset(&self, x: f64, y: f64) -> f64 {
-        self.offset.get_noise(x as f32, y as f32) as f64
+    pub fn sample_flat(&self, x: f64, y: f64) -> f64 {
+        Noise::sample(&self.root, Space::Flat, x, y, 0.)
     }
 
-    pub fn rng(&mut self) -> f64 {
-        self.uniform.rand::<f64>()
+    // Same as `sample_flat`, but repeating every `period` along x.
+    pub fn sample_wrapped(&self, x: f64, y: f64, period: f64) -> f64 {
+        Noise::sample(&self.root, Space::Cylinder(period), x, y, 0.)
     }
 
-    pub fn fractal_noise(&self, x: f64, y: f64) -> f64 {
-        let force = 0.25; // magic
-        let wavyness = 5e-1; // magic
+    // In 3D, for planets.
+    pub fn sample_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        Noise::sample(&self.root, Space::Sphere, x, y, z)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NoiseKind {
+    Simplex,
+    Perlin,
+    Value,
+    Cellular(CellularOutput),
+}
+
+impl Source {
+    fn validate(&self) -> Result<(), TerrainError> {
+        if self.octaves == 0 || !(self.frequency.is_finite() && self.frequency > 0.) {
+            return Err(TerrainError::InvalidNoiseGraph(format!(
+                "source `{}` needs at least one octave and a positive frequency",
+                self.seed
+            )));
+        }
+        Ok(())
+    }
+
+    fn build(&self, seed: u64, kind: NoiseKind) -> Result<FastNoise, TerrainError> {
+        self.validate()?;
+        let fractal = self.octaves > 1;
+        let mut noise = FastNoise::seeded(derive_seed(seed, &self.seed));
+        noise.set_noise_type(match kind {
+            NoiseKind::Simplex if fractal => NoiseType::SimplexFractal,
+            NoiseKind::Simplex => NoiseType::Simplex,
+            NoiseKind::Perlin if fractal => NoiseType::PerlinFractal,
+            NoiseKind::Perlin => NoiseType::Perlin,
+            NoiseKind::Value if fractal => NoiseType::ValueFractal,
+            NoiseKind::Value => NoiseType::Value,
+            NoiseKind::Cellular(_) => NoiseType::Cellular,
+        });
+        if fractal {
+            noise.set_fractal_type(FractalType::FBM);
+            noise.set_fractal_octaves(self.octaves as i32);
+            noise.set_fractal_gain(self.gain as f32);
+            noise.set_fractal_lacunarity(self.lacunarity as f32);
+        }
+        if let NoiseKind::Cellular(output) = kind {
+            noise.set_cellular_return_type(match output {
+                CellularOutput::CellValue => CellularReturnType::CellValue,
+                CellularOutput::Distance => CellularReturnType::Distance,
+            });
+        }
+        noise.set_frequency(self.frequency as f32);
+        Ok(noise)
+    }
+}
 
-        let theta = self.theta(x * force, y * force);
-        let length = self.offset(x * force, y * force);
+impl Worley {
+    // Distances to the nearest and second nearest points, one per grid cell.
+    fn distances(&self, p: [f64; 3], dimensions: usize) -> (f64, f64) {
+        let cell = p.map(libm::floor);
+        let (mut f1, mut f2) = (f64::INFINITY, f64::INFINITY);
+        let reach = |axis: usize| if axis < dimensions { -1..=1 } else { 0..=0 };
+        for dx in reach(0) {
+            for dy in reach(1) {
+                for dz in reach(2) {
+                    let neighbour = [
+                        cell[0] + dx as f64,
+                        cell[1] + dy as f64,
+                        cell[2] + dz as f64,
+                    ];
+                    let mut distance = 0.;
+                    for axis in 0..dimensions {
+                        let offset = (self.hash(neighbour, axis) - 0.5) * self.jitter;
+                        distance += (neighbour[axis] + 0.5 + offset - p[axis]).powi(2);
+                    }
+                    let distance = distance.sqrt();
+                    if distance < f1 {
+                        f2 = f1;
+                        f1 = distance;
+                    } else if distance < f2 {
+                        f2 = distance;
+                    }
+                }
+            }
+        }
+        (f1, f2)
+    }
+
+    // 0 to 1, the same on every platform.
+    fn hash(&self, cell: [f64; 3], axis: usize) -> f64 {
+        let mut h = self.seed ^ (axis as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        for &c in cell.iter() {
+            h = (h ^ (c as i64 as u64)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            h ^= h >> 31;
+        }
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    }
 
-        let x = x + theta.cos() * length * wavyness;
-        let y = y + theta.sin() * length * wavyness;
+    fn sample(&self, p: [f64; 3], dimensions: usize) -> f64 {
+        let (f1, f2) = self.distances(p, dimensions);
+        match self.output {
+            WorleyOutput::F1 => f1 * 2. - 1.,
+            WorleyOutput::F2MinusF1 => (f2 - f1) * 2. - 1.,
+            WorleyOutput::Crater => {
+                let radius = 0.4; // magic
+                let rim = 0.5; // magic
+                let r = f1 / radius;
+                if r < 1. {
+                    -1. + (1. + rim) * r * r
+                } else {
+                    rim * libm::exp(-(r - 1.) * 4.)
+                }
+            }
+            WorleyOutput::Mesa => {
+                let cliff = 0.15; // magic
+                let t = ((f2 - f1) / cliff).min(1.);
+                t * t * (3. - 2. * t) * 2. - 1.
+            }
+        }
+    }
+}
 
-        self.height(x, y)
+fn curve(points: &[[f64; 2]], x: f64) -> f64 {
+    let last = points.len() - 1;
+    if x <= points[0][0] {
+        return points[0][1];
+    }
+    if x >= points[last][0] {
+        return points[last][1];
     }
+    let k = points.iter().position(|p| p[0] > x).unwrap_or(last);
+    let ([x0, y0], [x1, y1]) = (points[k - 1], points[k]);
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
 }
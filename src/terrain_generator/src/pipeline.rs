@@ -0,0 +1,177 @@
+use wasm_bindgen::prelude::*;
+
+use super::domain::Domain;
+use super::error::TerrainError;
+use super::stage::{Stage, WorldContext};
+use super::terrain_generator::{to_js_value, TerrainGenerator, World};
+
+// Runs an ordered list of stages over a shared `WorldContext`, one iteration at a time.
+pub struct PipelineState {
+    stages: Vec<Box<dyn Stage>>,
+    stage: usize,
+    iteration: usize,
+    pub context: WorldContext,
+}
+
+impl PipelineState {
+    pub fn new(
+        radius: f64,
+        sea_level: f64,
+        domain: Domain,
+        stages: Vec<Box<dyn Stage>>,
+    ) -> PipelineState {
+        PipelineState {
+            stages,
+            stage: 0,
+            iteration: 0,
+            context: WorldContext::new(radius, sea_level, domain),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.stage >= self.stages.len()
+    }
+
+    // Name of the stage that will run on the next `step`.
+    pub fn stage_name(&self) -> &str {
+        match self.stages.get(self.stage) {
+            Some(stage) => stage.name(),
+            None => "done",
+        }
+    }
+
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    // Run one iteration of the current stage, moving on to the next stage once it is exhausted.
+    // After the last one, reports `"done"`, too late to be cancelled.
+    pub fn step(&mut self, gen: &mut TerrainGenerator) -> Result<(), TerrainError> {
+        let stage = match self.stages.get_mut(self.stage) {
+            Some(stage) => stage,
+            None => return Ok(()),
+        };
+        let iterations = stage.iterations();
+
+        gen.progress.report(
+            stage.name(),
+            self.iteration as f64 / iterations.max(1) as f64,
+        )?;
+        if self.iteration < iterations {
+            stage.run(&mut self.context, gen, self.iteration)?;
+        }
+
+        self.iteration += 1;
+        if self.iteration >= iterations {
+            self.stage += 1;
+            self.iteration = 0;
+        }
+        if self.is_done() {
+            gen.progress.notify("done", 1.);
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self, gen: &mut TerrainGenerator) -> Result<(), TerrainError> {
+        while !self.is_done() {
+            self.step(gen)?;
+        }
+        Ok(())
+    }
+
+    // Runs whatever stages are left first.
+    pub fn into_world(mut self, gen: &mut TerrainGenerator) -> Result<World, TerrainError> {
+        self.run(gen)?;
+        self.context.into_world()
+    }
+}
+
+// Owns its own generator, so JS can hold on to it between animation frames.
+#[wasm_bindgen]
+pub struct Pipeline {
+    generator: TerrainGenerator,
+    state: PipelineState,
+}
+
+#[wasm_bindgen]
+impl Pipeline {
+    // Every setting at its default. `TerrainGenerator::pipeline` steps through the world a
+    // configured generator would build.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: Option<u64>, radius: f64, sea_level: f64) -> Pipeline {
+        TerrainGenerator::new(seed).pipeline(radius, sea_level)
+    }
+
+    pub fn planet(seed: Option<u64>, radius: f64, sea_level: f64) -> Pipeline {
+        TerrainGenerator::new(seed).planet_pipeline(radius, sea_level)
+    }
+
+    // Advance one stage, or one erosion iteration. Returns `false` once there is nothing left.
+    pub fn step(&mut self) -> Result<bool, TerrainError> {
+        if self.state.is_done() {
+            return Ok(false);
+        }
+        self.state.step(&mut self.generator)?;
+        Ok(!self.state.is_done())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn stage(&self) -> String {
+        self.state.stage_name().to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn iteration(&self) -> usize {
+        self.state.iteration()
+    }
+
+    #[wasm_bindgen(getter, js_name = "isDone")]
+    pub fn is_done(&self) -> bool {
+        self.state.is_done()
+    }
+
+    pub fn heights(&self) -> Vec<f64> {
+        self.state.context.heights.clone()
+    }
+
+    #[wasm_bindgen(js_name = "cellHeights")]
+    pub fn cell_heights(&self) -> Vec<f64> {
+        self.state.context.cell_heights.clone()
+    }
+
+    pub fn as_js_value(&self) -> Result<JsValue, TerrainError> {
+        to_js_value(&self.state.context)
+    }
+
+    // Run whatever is left and hand back the finished world.
+    pub fn finish(mut self) -> Result<World, TerrainError> {
+        self.state.into_world(&mut self.generator)
+    }
+}
+
+impl Pipeline {
+    pub fn with_stages(
+        generator: TerrainGenerator,
+        radius: f64,
+        sea_level: f64,
+        stages: Vec<Box<dyn Stage>>,
+    ) -> Pipeline {
+        let domain = generator.domain;
+        Pipeline {
+            generator,
+            state: PipelineState::new(radius, sea_level, domain, stages),
+        }
+    }
+
+    pub fn state(&self) -> &PipelineState {
+        &self.state
+    }
+
+    pub fn context(&self) -> &WorldContext {
+        &self.state.context
+    }
+
+    pub fn generator_mut(&mut self) -> &mut TerrainGenerator {
+        &mut self.generator
+    }
+}
@@ -1,4 +1,9 @@
+use super::domain::Domain;
+use super::error::TerrainError;
 use super::terrain_generator::TerrainGenerator;
+use super::tile::Tile;
+use bracket_random::prelude::RandomNumberGenerator;
+use std::collections::HashMap;
 use std::f64::consts::PI;
 
 extern crate web_sys;
@@ -10,41 +15,221 @@ macro_rules! log {
     }
 }
 
-pub fn disc_sample(radius: f64, sea_level: f64, gen: &mut TerrainGenerator) -> Vec<f64> {
+// Background grid of `size` × `size` cells, each holding the samples that fall inside it.
+// When the domain wraps, the first and last columns are neighbours.
+struct Grid {
+    cells: Vec<Vec<[f64; 2]>>,
+    size: f64,
+    cols: usize,
+    rows: usize,
+    width: f64,
+    height: f64,
+    wrap: bool,
+}
+
+impl Grid {
+    fn new(size: f64, domain: &Domain) -> Grid {
+        let cols = (domain.width / size) as usize;
+        let rows = (domain.height / size) as usize;
+        Grid {
+            cells: vec![vec![]; rows * cols],
+            size,
+            cols,
+            rows,
+            width: domain.width,
+            height: domain.height,
+            wrap: domain.wrap,
+        }
+    }
+
+    fn push(&mut self, col: usize, row: usize, sample: [f64; 2]) {
+        self.cells[col + row * self.cols].push(sample);
+    }
+
+    // Column and row of (`x`, `y`), or `None` if it is off the grid.
+    fn cell(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        // If out of lower bounds, keep looking.
+        if 0.0 > x || 0.0 > y {
+            return None;
+        }
+
+        let col = (x / self.size) as usize;
+        let row = (y / self.size) as usize;
+
+        // If out of upper bounds, keep looking.
+        if row >= self.rows {
+            return None;
+        }
+        if col >= self.cols {
+            // The last column of a wrapping grid stretches to the seam.
+            return if self.wrap && x < self.width {
+                Some((self.cols - 1, row))
+            } else {
+                None
+            };
+        }
+        Some((col, row))
+    }
+
+    fn distance(&self, a: &[f64; 2], b: &[f64; 2]) -> f64 {
+        let mut dx = (a[0] - b[0]).abs();
+        if self.wrap {
+            dx = dx.min(self.width - dx);
+        }
+        (dx.powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+    }
+}
+
+pub fn disc_sample(
+    radius: f64,
+    sea_level: f64,
+    gen: &mut TerrainGenerator,
+) -> Result<Vec<f64>, TerrainError> {
     // Stuff
 
+    let domain = gen.domain;
+    if !(radius > 0.0 && radius <= 2.0_f64.sqrt() * domain.shortest_side()) {
+        return Err(TerrainError::InvalidRadius(radius));
+    }
+
     let size = radius / (2.0_f64).sqrt();
-    let cols = (1.0 / size) as usize;
-    let rows = (1.0 / size) as usize;
+    let mut grid = Grid::new(size, &domain);
+    let mut active: Vec<[f64; 2]> = Vec::new();
+    let mut points: Vec<f64> = Vec::new();
+
+    add_borders(&mut grid, &mut active, &mut points, &domain);
 
-    let grid: Vec<Vec<[f64; 2]>> = vec![vec![]; rows * cols];
-    let active: Vec<[f64; 2]> = Vec::new();
-    let points: Vec<f64> = Vec::new();
+    let mut rng = gen.stage_rng("poisson");
+    fill(
+        &mut grid,
+        active,
+        points,
+        &mut rng,
+        (0., 0.),
+        sea_level,
+        gen,
+    )
+}
+
+// Points of one tile of an endless map, in world coordinates. Each tile has a seed of its own and
+// no border points, so a tile comes out the same whichever tiles are sampled around it.
+pub fn tile_sample(
+    radius: f64,
+    sea_level: f64,
+    gen: &mut TerrainGenerator,
+    tile: &Tile,
+) -> Result<Vec<f64>, TerrainError> {
+    let domain = Domain {
+        wrap: false,
+        ..gen.domain
+    };
+    if !(radius > 0.0 && radius <= 2.0_f64.sqrt() * domain.shortest_side()) {
+        return Err(TerrainError::InvalidRadius(radius));
+    }
 
-    let destruct = add_borders(grid, active, points, size, cols, rows);
-    let mut grid = destruct.0;
-    let mut active = destruct.1;
-    let mut points = destruct.2;
+    let size = radius / (2.0_f64).sqrt();
+    let mut grid = Grid::new(size, &domain);
+    let bounds = tile.bounds(&domain);
+
+    let mut rng = gen.stage_rng(&format!("poisson {} {}", tile.x, tile.y));
+    fill(
+        &mut grid,
+        vec![],
+        vec![],
+        &mut rng,
+        (bounds[0], bounds[1]),
+        sea_level,
+        gen,
+    )
+}
+
+// Put the samples of neighbouring tiles together. Tiles are sampled on their own, so points either
+// side of an edge can end up too close. The lower tile (by `Tile`'s ordering) keeps its points, the
+// other drops them, which every tile that sees both decides the same way.
+pub fn merge_tiles(
+    samples: &[(Tile, Vec<f64>)],
+    radius: f64,
+    sea_level: f64,
+    gen: &TerrainGenerator,
+) -> Vec<f64> {
+    let size = radius / (2.0_f64).sqrt();
+    let bucket = |x: f64, y: f64| ((x / size).floor() as i64, (y / size).floor() as i64);
+
+    let mut buckets = HashMap::new();
+    for (tile, points) in samples.iter() {
+        for p in points.chunks_exact(2) {
+            buckets
+                .entry(bucket(p[0], p[1]))
+                .or_insert_with(Vec::new)
+                .push((*tile, [p[0], p[1]]));
+        }
+    }
+
+    let mut merged = Vec::new();
+    for (tile, points) in samples.iter() {
+        for p in points.chunks_exact(2) {
+            let (col, row) = bucket(p[0], p[1]);
+            let rivals = (-1..=1)
+                .flat_map(|i| (-1..=1).map(move |j| (col + i, row + j)))
+                .filter_map(|cell| buckets.get(&cell))
+                .flatten()
+                .filter(|(other, _)| other < tile)
+                .map(|(_, q)| libm::hypot(q[0] - p[0], q[1] - p[1]))
+                .filter(|&distance| distance < size)
+                .collect::<Vec<_>>();
+
+            // Only look up the spacing when there's a rival at all, it costs a noise sample.
+            let too_close = !rivals.is_empty() && {
+                let min_offset = size * gen.density.spacing(p[0], p[1], sea_level, gen);
+                rivals.iter().any(|&distance| distance < min_offset)
+            };
+            if !too_close {
+                merged.extend(p.iter());
+            }
+        }
+    }
+    merged
+}
 
-    let x = gen.noise.rng();
-    let y = gen.noise.rng();
+// Grow `points` from the `active` samples until there's no room left, starting with one random
+// sample. The grid is in coordinates local to `origin`, `points` are not.
+fn fill(
+    grid: &mut Grid,
+    mut active: Vec<[f64; 2]>,
+    mut points: Vec<f64>,
+    rng: &mut RandomNumberGenerator,
+    origin: (f64, f64),
+    sea_level: f64,
+    gen: &mut TerrainGenerator,
+) -> Result<Vec<f64>, TerrainError> {
+    let size = grid.size;
+    let x = rng.rand::<f64>() * grid.width;
+    let y = rng.rand::<f64>() * grid.height;
     let sample = [x, y];
-    let col = ((x / size) as usize).min(cols - 1);
-    let row = ((y / size) as usize).min(rows - 1);
-    grid[col + row * cols].push(sample);
+    let col = ((x / size) as usize).min(grid.cols - 1);
+    let row = ((y / size) as usize).min(grid.rows - 1);
+    grid.push(col, row, sample);
     active.push(sample);
     points.extend(sample.iter());
 
-    let offset_magnitude = |h| {
-        let n = if h > sea_level { h } else { 1.0 - h };
-        n
-    };
+    // Roughly one point per grid cell, good enough for a progress estimate.
+    let expected = (grid.cols * grid.rows) as f64;
+    let mut iterations = 0;
 
     while active.len() > 0 {
-        let rand_i = (gen.noise.rng() * active.len() as f64) as usize;
+        iterations += 1;
+        if iterations % 256 == 0 {
+            gen.progress
+                .report("points", points.len() as f64 / 2. / expected)?;
+        }
+
+        let rand_i = (rng.rand::<f64>() * active.len() as f64) as usize;
         let point = &active[rand_i];
-        let min_offset = size * offset_magnitude(gen.noise_single(point[0], point[1]));
-        let new_points = sample_poisson_points(30, size, min_offset, &point, &mut grid, gen);
+        let spacing = gen
+            .density
+            .spacing(point[0] + origin.0, point[1] + origin.1, sea_level, gen);
+        let min_offset = size * spacing;
+        let new_points = sample_poisson_points(30, min_offset, point, grid, rng);
 
         for sample in new_points.iter() {
             points.extend(sample.iter());
@@ -53,69 +238,57 @@ pub fn disc_sample(radius: f64, sea_level: f64, gen: &mut TerrainGenerator) -> V
         active.remove(rand_i);
     }
 
-    points
+    for (i, x) in points.iter_mut().enumerate() {
+        *x += if i % 2 == 0 { origin.0 } else { origin.1 };
+    }
+    Ok(points)
 }
 
 fn sample_poisson_points(
     k: usize,
-    size: f64,
     min_offset: f64,
     point: &[f64; 2],
-    grid: &mut Vec<Vec<[f64; 2]>>,
-    gen: &mut TerrainGenerator,
+    grid: &mut Grid,
+    rng: &mut RandomNumberGenerator,
 ) -> Vec<[f64; 2]> {
     let mut new_points: Vec<[f64; 2]> = vec![];
 
-    let cols = (1.0 / size) as usize;
-    let rows = (1.0 / size) as usize;
-
     for _ in 0..k {
         // Get a sample at some random angle and distance from `point`
-        let theta = gen.noise.rng() * PI * 2.0;
-        let offset = size + gen.noise.rng() * min_offset;
-        let x = point[0] + theta.cos() * offset;
-        let y = point[1] + theta.sin() * offset;
-
-        // If out of lower bounds, keep looking.
-        if 0.0 > x || 0.0 > y {
-            continue;
+        let theta = rng.rand::<f64>() * PI * 2.0;
+        let offset = grid.size + rng.rand::<f64>() * min_offset;
+        let mut x = point[0] + libm::cos(theta) * offset;
+        let y = point[1] + libm::sin(theta) * offset;
+        if grid.wrap {
+            x = x.rem_euclid(grid.width);
         }
 
         let sample = [x, y];
-        let col = (x / size) as usize;
-        let row = (y / size) as usize;
+        let (col, row) = match grid.cell(x, y) {
+            Some(cell) => cell,
+            None => continue,
+        };
 
-        // If out of upper bounds, keep looking.
-        if row >= rows || col >= cols {
-            continue;
-        }
-
-        if check_sample(row, col, cols, rows, &sample, &grid, min_offset) == false {
+        if !check_sample(row, col, &sample, grid, min_offset) {
             continue; // Check if too close to existing samples. If point is not valid, keep looking.
         }
         // push sample in
-        grid[col + row * cols].push(sample);
+        grid.push(col, row, sample);
         new_points.push(sample);
     }
 
     new_points
 }
 
-fn check_sample(
-    row: usize,
-    col: usize,
-    cols: usize,
-    rows: usize,
-    sample: &[f64; 2],
-    grid: &Vec<Vec<[f64; 2]>>,
-    min_offset: f64,
-) -> bool {
-    let euclidean =
-        |a: &[f64; 2], b: &[f64; 2]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+fn check_sample(row: usize, col: usize, sample: &[f64; 2], grid: &Grid, min_offset: f64) -> bool {
+    let cols = grid.cols;
+    let rows = grid.rows;
 
     'i_loop: for i in ([-1, 0, 1] as [i8; 3]).iter() {
         'j_loop: for j in ([-1, 0, 1] as [i8; 3]).iter() {
             let neighbor_col = match i {
+                -1 if grid.wrap => Some((col + cols - 1) % cols),
+                1 if grid.wrap => Some((col + 1) % cols),
                 -1 => col.checked_sub(1),
                 1 => col.checked_add(1),
                 _ => Some(col),
@@ -149,8 +322,8 @@ fn check_sample(
 
             let neighbor_i = neighbor_col.wrapping_add(cols * neighbor_row);
 
-            for neighbor in grid[neighbor_i].iter() {
-                let dist = euclidean(&sample, neighbor);
+            for neighbor in grid.cells[neighbor_i].iter() {
+                let dist = grid.distance(sample, neighbor);
                 if dist < min_offset {
                     return false;
                 }
@@ -161,61 +334,72 @@ fn check_sample(
 }
 
 fn add_borders(
-    mut grid: Vec<Vec<[f64; 2]>>,
-    mut active: Vec<[f64; 2]>,
-    mut points: Vec<f64>,
-    size: f64,
-    cols: usize,
-    rows: usize,
-) -> (Vec<Vec<[f64; 2]>>, Vec<[f64; 2]>, Vec<f64>) {
-    let size = size / 2.0;
-    let offset = 5e-2;
-    let cx = 1.0 / 2.0;
-    let cy = 1.0 / 2.0;
+    grid: &mut Grid,
+    active: &mut Vec<[f64; 2]>,
+    points: &mut Vec<f64>,
+    domain: &Domain,
+) {
+    let cols = grid.cols;
+    let rows = grid.rows;
+    let size = grid.size / 2.0;
+    let offset = 5e-2 * domain.shortest_side();
+    let (width, height) = (domain.width, domain.height);
+    let (cx, cy) = domain.center();
 
-    // Top
-    for _x in 0..=(1.0 / size) as usize {
-        let x = _x as f64 * size;
-        let y = offset * -(x - cx).abs().cos();
-        let pos = [x, y];
-        let i = (x / 2.0 / size) as usize;
-        grid[i].push(pos);
+    // A wrapping map has no left and right edges, and x = 0 is the same place as x = width.
+    let across = if domain.wrap {
+        0..(width / size).ceil() as usize
+    } else {
+        0..(width / size) as usize + 1
+    };
+    // The borders bow outwards, except on a wrapping map where any bend would leave a dent at the
+    // seam for the triangulation to fill.
+    let bend = |d: f64, length: f64| {
+        if domain.wrap {
+            1.
+        } else {
+            libm::cos((d / length).abs())
+        }
+    };
+    let mut push = |i: usize, pos: [f64; 2]| {
+        grid.cells[i].push(pos);
         active.push(pos);
         points.extend(pos.iter());
+    };
+
+    // Top
+    for _x in across.clone() {
+        let x = _x as f64 * size;
+        let y = offset * -bend(x - cx, width);
+        let i = ((x / 2.0 / size) as usize).min(cols - 1);
+        push(i, [x, y]);
     }
 
-    // Left
-    for _y in 0..=(1.0 / size) as usize {
-        let y = _y as f64 * size;
-        let x = offset * -(y - cy).abs().cos();
-        let pos = [x, y];
-        let j = ((y / 2.0 / size) as usize).min(cols - 1);
-        grid[j * cols].push(pos);
-        active.push(pos);
-        points.extend(pos.iter());
+    if !domain.wrap {
+        // Left
+        for _y in 0..=(height / size) as usize {
+            let y = _y as f64 * size;
+            let x = offset * -libm::cos(((y - cy) / height).abs());
+            let j = ((y / 2.0 / size) as usize).min(rows - 1);
+            push(j * cols, [x, y]);
+        }
     }
 
     // Bottom
-    for _x in 0..=(1.0 / size) as usize {
+    for _x in across {
         let x = _x as f64 * size;
-        let y = 1.0 + offset * (x - cx).abs().cos();
-        let pos = [x, y];
+        let y = height + offset * bend(x - cx, width);
         let i = ((x / 2.0 / size) as usize).min(cols - 1);
-        grid[i + (rows - 1) * cols].push(pos);
-        active.push(pos);
-        points.extend(pos.iter());
+        push(i + (rows - 1) * cols, [x, y]);
     }
 
-    // Right
-    for _y in 0..=(1.0 / size) as usize {
-        let y = _y as f64 * size;
-        let x = 1.0 + offset * (y - cy).abs().cos();
-        let pos = [x, y];
-        let j = ((y / 2.0 / size) as usize).min(cols - 1);
-        grid[cols - 1 + j * cols].push(pos);
-        active.push(pos);
-        points.extend(pos.iter());
+    if !domain.wrap {
+        // Right
+        for _y in 0..=(height / size) as usize {
+            let y = _y as f64 * size;
+            let x = width + offset * libm::cos(((y - cy) / height).abs());
+            let j = ((y / 2.0 / size) as usize).min(rows - 1);
+            push(cols - 1 + j * cols, [x, y]);
+        }
     }
-
-    (grid, active, points)
 }
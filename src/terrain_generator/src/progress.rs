@@ -0,0 +1,94 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+use super::error::TerrainError;
+
+// Shared flag checked between and within stages. Clones share the same flag, so a token handed
+// out before generation can be cancelled from a progress callback (or another thread).
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[wasm_bindgen]
+impl CancellationToken {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    #[wasm_bindgen(getter, js_name = "isCancelled")]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+type Callback = Rc<RefCell<dyn FnMut(&str, f64)>>;
+
+// Clones share the callback and the token.
+#[derive(Clone, Default)]
+pub struct Progress {
+    callback: Option<Callback>,
+    token: CancellationToken,
+}
+
+impl Progress {
+    pub fn set_callback<F: FnMut(&str, f64) + 'static>(&mut self, callback: F) {
+        self.callback = Some(Rc::new(RefCell::new(callback)));
+    }
+
+    pub fn clear_callback(&mut self) {
+        self.callback = None;
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn set_token(&mut self, token: CancellationToken) {
+        self.token = token;
+    }
+
+    // Report `fraction` (0 to 1) of `stage` done, then bail out if we have been cancelled.
+    pub fn report(&mut self, stage: &str, fraction: f64) -> Result<(), TerrainError> {
+        self.notify(stage, fraction);
+        self.check()
+    }
+
+    // Report without checking for cancellation, for when there is nothing left to cancel.
+    pub fn notify(&mut self, stage: &str, fraction: f64) {
+        if let Some(callback) = self.callback.as_ref() {
+            (callback.borrow_mut())(stage, fraction.clamp(0., 1.));
+        }
+    }
+
+    pub fn check(&self) -> Result<(), TerrainError> {
+        if self.token.is_cancelled() {
+            Err(TerrainError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("callback", &self.callback.is_some())
+            .field("token", &self.token)
+            .finish()
+    }
+}
@@ -0,0 +1,129 @@
+use super::error::TerrainError;
+
+// A grid of values stretched over the map, row by row from the top left.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Raster {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+impl Raster {
+    pub fn new(width: usize, height: usize, data: Vec<f64>) -> Result<Raster, TerrainError> {
+        if width == 0 || height == 0 || width.checked_mul(height) != Some(data.len()) {
+            return Err(TerrainError::InvalidRaster {
+                width,
+                height,
+                len: data.len(),
+            });
+        }
+        Ok(Raster {
+            width,
+            height,
+            data,
+        })
+    }
+
+    // A grayscale PNG, 0 for black and 1 for white. Colour images are turned grey by luminance,
+    // and alpha is ignored.
+    pub fn from_png(bytes: &[u8]) -> Result<Raster, TerrainError> {
+        let invalid = |e: png::DecodingError| TerrainError::InvalidHeightmap(e.to_string());
+        let mut decoder = png::Decoder::new(bytes);
+        // Palettes and bit depths under 8 come out as plain 8 bit samples.
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(invalid)?;
+
+        let values = match info.bit_depth {
+            png::BitDepth::Sixteen => buffer[..info.buffer_size()]
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / u16::MAX as f64)
+                .collect::<Vec<_>>(),
+            _ => buffer[..info.buffer_size()]
+                .iter()
+                .map(|&b| b as f64 / u8::MAX as f64)
+                .collect(),
+        };
+        let data = match info.color_type {
+            png::ColorType::Rgb | png::ColorType::Rgba => values
+                .chunks_exact(info.color_type.samples())
+                .map(|c| 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2])
+                .collect(),
+            _ => values
+                .chunks_exact(info.color_type.samples())
+                .map(|c| c[0])
+                .collect(),
+        };
+        Raster::new(info.width as usize, info.height as usize, data)
+    }
+
+    // Headerless samples, row by row from the top left, as painting programs export them. `bits`
+    // is 8, or 16 for little endian pairs of bytes.
+    pub fn from_raw(
+        width: usize,
+        height: usize,
+        bytes: &[u8],
+        bits: u8,
+    ) -> Result<Raster, TerrainError> {
+        let size = match bits {
+            8 | 16 => bits as usize / 8,
+            _ => {
+                return Err(TerrainError::InvalidHeightmap(format!(
+                    "samples must be 8 or 16 bits, got {}",
+                    bits
+                )))
+            }
+        };
+        let needed = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(size))
+            .ok_or_else(|| {
+                TerrainError::InvalidHeightmap(format!("{}×{} samples is too big", width, height))
+            })?;
+        if bytes.len() != needed {
+            return Err(TerrainError::InvalidHeightmap(format!(
+                "{}×{} samples of {} bits need {} bytes, got {}",
+                width,
+                height,
+                bits,
+                needed,
+                bytes.len()
+            )));
+        }
+        let data = bytes
+            .chunks_exact(size)
+            .map(|b| match b {
+                [b] => *b as f64 / u8::MAX as f64,
+                b => u16::from_le_bytes([b[0], b[1]]) as f64 / u16::MAX as f64,
+            })
+            .collect();
+        Raster::new(width, height, data)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get(&self, col: usize, row: usize) -> f64 {
+        self.data[col.min(self.width - 1) + row.min(self.height - 1) * self.width]
+    }
+
+    // Bilinear sample, with `u` and `v` clamped to [0, 1].
+    pub fn sample(&self, u: f64, v: f64) -> f64 {
+        let x = u.clamp(0., 1.) * (self.width - 1) as f64;
+        let y = v.clamp(0., 1.) * (self.height - 1) as f64;
+        let col = x.floor() as usize;
+        let row = y.floor() as usize;
+        let tx = x - col as f64;
+        let ty = y - row as f64;
+
+        let top = self.get(col, row) * (1. - tx) + self.get(col + 1, row) * tx;
+        let bottom = self.get(col, row + 1) * (1. - tx) + self.get(col + 1, row + 1) * tx;
+        top * (1. - ty) + bottom * ty
+    }
+}
@@ -0,0 +1,73 @@
+use super::domain::Domain;
+use super::error::TerrainError;
+use super::progress::Progress;
+use super::voronoi::{VertexMode, Voronoi};
+
+// One step of Lloyd's algorithm: move each point `strength` of the way to the centroid of its
+// voronoi cell. 1 is a full step, lower keeps more of the original irregularity.
+// With `weight`, centroids are weighted by it, so points settle closer together where it is high.
+// Points whose cell is entirely off the map, like the border points, stay where they are.
+// Stops early if `progress` is cancelled.
+pub fn lloyd_step(
+    points: &[f64],
+    domain: &Domain,
+    strength: f64,
+    weight: Option<&dyn Fn(f64, f64) -> f64>,
+    progress: &Progress,
+) -> Result<Vec<f64>, TerrainError> {
+    let mut voronoi = if domain.wrap {
+        Voronoi::new_wrapping(points.to_vec(), domain.width, VertexMode::Circumcenter)?
+    } else {
+        Voronoi::new(points.to_vec(), VertexMode::Circumcenter)?
+    };
+    progress.check()?;
+    voronoi.clip(domain.bounds(), domain.wrap);
+
+    let mut relaxed = points.to_vec();
+    for (i, polygon) in voronoi.cell_polygons.iter().enumerate() {
+        if i % 1024 == 0 {
+            progress.check()?;
+        }
+        let (x, y) = (points[i * 2], points[i * 2 + 1]);
+        if let Some((cx, cy)) = centroid(polygon, weight) {
+            let mut new_x = x + (cx - x) * strength;
+            if domain.wrap {
+                new_x = new_x.rem_euclid(domain.width);
+            }
+            relaxed[i * 2] = new_x;
+            relaxed[i * 2 + 1] = y + (cy - y) * strength;
+        }
+    }
+    Ok(relaxed)
+}
+
+// Fan the polygon out into triangles from its first corner, each weighted by its area (and by
+// `weight` at its centroid).
+fn centroid(polygon: &[f64], weight: Option<&dyn Fn(f64, f64) -> f64>) -> Option<(f64, f64)> {
+    let n = polygon.len() / 2;
+    if n < 3 {
+        return None;
+    }
+    let corner = |k: usize| (polygon[k * 2], polygon[k * 2 + 1]);
+    let a = corner(0);
+
+    let (mut total, mut sum_x, mut sum_y) = (0., 0., 0.);
+    for k in 1..n - 1 {
+        let (b, c) = (corner(k), corner(k + 1));
+        let area = ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.;
+        let (x, y) = ((a.0 + b.0 + c.0) / 3., (a.1 + b.1 + c.1) / 3.);
+        let mass = match weight {
+            Some(weight) => area * weight(x, y),
+            None => area,
+        };
+        total += mass;
+        sum_x += mass * x;
+        sum_y += mass * y;
+    }
+
+    if total > 0. && total.is_finite() {
+        Some((sum_x / total, sum_y / total))
+    } else {
+        None
+    }
+}
@@ -1,4 +1,5 @@
-use super::erosion::get_flux;
+use super::erosion::{check_heights, get_flux};
+use super::error::TerrainError;
 
 type River = Vec<(usize, f64)>;
 
@@ -37,7 +38,7 @@ pub fn get_river(
 
     // Check all neighbors by reverse flux order
     let mut neighbors = adjacent[i].clone();
-    neighbors.sort_unstable_by(|&a, &b| flux[a].partial_cmp(&flux[b]).unwrap().reverse());
+    neighbors.sort_unstable_by(|&a, &b| flux[a].total_cmp(&flux[b]).reverse());
 
     for neighbor in neighbors {
         if visited[neighbor] {
@@ -89,11 +90,12 @@ pub fn get_rivers(
     sea_level: f64,
     voronoi_cells: &Vec<Vec<usize>>,
     cell_heights: &Vec<f64>,
-) -> Vec<River> {
-    let flux = get_flux(heights, adjacent);
+) -> Result<Vec<River>, TerrainError> {
+    check_heights(cell_heights)?;
+    let flux = get_flux(heights, adjacent)?;
 
     let mut points_by_height = (0..heights.len()).collect::<Vec<usize>>();
-    points_by_height.sort_unstable_by(|a, b| heights[*a].partial_cmp(&heights[*b]).unwrap());
+    points_by_height.sort_unstable_by(|a, b| heights[*a].total_cmp(&heights[*b]));
 
     let mut visited = vec![false; heights.len()];
     let mut rivers: Vec<River> = Vec::new();
@@ -118,8 +120,8 @@ pub fn get_rivers(
         rivers.append(&mut new_tributaries);
     }
 
-    rivers
+    Ok(rivers
         .into_iter()
         .filter(|r| r.len() > 1)
-        .collect::<Vec<River>>()
+        .collect::<Vec<River>>())
 }
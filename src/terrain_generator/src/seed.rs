@@ -0,0 +1,73 @@
+//! Seeds and hashing for reproducible worlds.
+//!
+//! The determinism contract: the same seed, parameters and stage list produce a bit-identical
+//! `World`, natively and in the wasm build, for a given version of this crate.
+//!
+//! - Seeds are full `u64`s (a `BigInt` from JS).
+//! - Every stage that needs randomness derives its own seed with `derive_seed(seed, stage)`, so
+//!   adding randomness to one stage never reshuffles another.
+//! - Trigonometry, logarithms and `hypot` go through the pure Rust `libm` crate instead of the
+//!   platform's maths library, which is where native and wasm builds used to disagree.
+//! - Noise is evaluated in `f32` by `bracket-noise`. That only involves IEEE arithmetic, so it
+//!   rounds the same way everywhere.
+//! - `World::hash` is a stable hash of the whole world, used by the golden tests in
+//!   `tests/determinism.rs`. Those hashes change whenever generation changes on purpose.
+
+pub const DEFAULT_SEED: u64 = 123456;
+
+// FNV-1a. Unlike `DefaultHasher`, it is the same on every platform and Rust version.
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> StableHasher {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    pub fn write_str(&mut self, value: &str) {
+        self.write_usize(value.len());
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> StableHasher {
+        StableHasher::new()
+    }
+}
+
+// SplitMix64 finaliser, spreads the FNV output over all 64 bits.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub fn derive_seed(seed: u64, stage: &str) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write_u64(seed);
+    hasher.write_str(stage);
+    mix(hasher.finish())
+}
@@ -0,0 +1,278 @@
+use delaunator::EMPTY;
+
+use super::sphere::{self, Sphere};
+use super::voronoi::{next_halfedge, Voronoi};
+
+// Finds which triangle or cell a position on the map is in, by walking the delaunay triangulation
+// from a nearby point. A coarse grid over the map picks the point to start from.
+//
+// Flat positions are lifted to `z = 1`, so the same triple products that work on a planet's unit
+// sphere give orientations and barycentric weights on a flat map too.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SpatialIndex {
+    bounds: [f64; 4],
+    wrap: bool,
+    cols: usize,
+    rows: usize,
+    // A point in, or close to, each grid cell.
+    starts: Vec<usize>,
+    // Where each point's fan starts in `Voronoi::voronoi_triangles`, counted in triangles. One
+    // longer than the number of points.
+    fans: Vec<usize>,
+}
+
+impl SpatialIndex {
+    // `bounds` as in `World`. For a planet that is its map, see `sphere::MAP_BOUNDS`.
+    pub fn new(voronoi: &Voronoi, bounds: [f64; 4], wrap: bool) -> SpatialIndex {
+        let points = &voronoi.delaunay.points;
+        let n = points.len() / 2;
+        let [xmin, ymin, xmax, ymax] = bounds;
+        let aspect = (xmax - xmin) / (ymax - ymin);
+        let cols = ((n as f64 * aspect).sqrt().ceil() as usize).max(1);
+        let rows = ((n as f64 / aspect).sqrt().ceil() as usize).max(1);
+
+        let mut index = SpatialIndex {
+            bounds,
+            wrap,
+            cols,
+            rows,
+            starts: vec![EMPTY; cols * rows],
+            fans: Vec::with_capacity(n + 1),
+        };
+        for i in 0..n {
+            let cell = index.grid_cell(points[i * 2], points[i * 2 + 1]);
+            if index.starts[cell] == EMPTY {
+                index.starts[cell] = i;
+            }
+        }
+        // Empty grid cells start from the closest filled one before or after them.
+        let mut last = EMPTY;
+        for k in (0..index.starts.len()).chain((0..index.starts.len()).rev()) {
+            if index.starts[k] == EMPTY {
+                index.starts[k] = last;
+            } else {
+                last = index.starts[k];
+            }
+        }
+
+        for (k, fan) in voronoi.voronoi_triangles.chunks_exact(3).enumerate() {
+            while index.fans.len() <= fan[0] {
+                index.fans.push(k);
+            }
+        }
+        while index.fans.len() <= n {
+            index.fans.push(voronoi.voronoi_triangles.len() / 3);
+        }
+        index
+    }
+
+    // Whether the east and west edges of the map meet.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    fn grid_cell(&self, x: f64, y: f64) -> usize {
+        let [xmin, ymin, xmax, ymax] = self.bounds;
+        let col = ((x - xmin) / (xmax - xmin) * self.cols as f64).floor();
+        let row = ((y - ymin) / (ymax - ymin) * self.rows as f64).floor();
+        let col = col.max(0.).min((self.cols - 1) as f64) as usize;
+        let row = row.max(0.).min((self.rows - 1) as f64) as usize;
+        row * self.cols + col
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        let [xmin, ymin, xmax, ymax] = self.bounds;
+        (xmin..=xmax).contains(&x) && (ymin..=ymax).contains(&y)
+    }
+
+    // Where on the map `(x, y)` is, lifted as described above.
+    fn query(&self, sphere: Option<&Sphere>, x: f64, y: f64) -> [f64; 3] {
+        match sphere {
+            Some(_) => sphere::from_map_position(x, y),
+            None => [x, y, 1.],
+        }
+    }
+
+    // A flat position, moved by whole map widths to be next to `query` when wrapping.
+    fn lift(&self, x: f64, y: f64, query: &[f64; 3]) -> [f64; 3] {
+        let width = self.bounds[2] - self.bounds[0];
+        let x = if self.wrap {
+            x - ((x - query[0]) / width).round() * width
+        } else {
+            x
+        };
+        [x, y, 1.]
+    }
+
+    fn point(
+        &self,
+        voronoi: &Voronoi,
+        sphere: Option<&Sphere>,
+        p: usize,
+        q: &[f64; 3],
+    ) -> [f64; 3] {
+        match sphere {
+            Some(sphere) => position(&sphere.positions, p),
+            None => {
+                let points = &voronoi.delaunay.points;
+                self.lift(points[p * 2], points[p * 2 + 1], q)
+            }
+        }
+    }
+
+    fn corner(
+        &self,
+        voronoi: &Voronoi,
+        sphere: Option<&Sphere>,
+        t: usize,
+        q: &[f64; 3],
+    ) -> [f64; 3] {
+        match sphere {
+            Some(sphere) => position(&sphere.circumcenters, t),
+            None => {
+                let centers = &voronoi.circumcenters;
+                self.lift(centers[t * 2], centers[t * 2 + 1], q)
+            }
+        }
+    }
+
+    // The delaunay triangle `(x, y)` is in, `None` off the map or outside the hull.
+    pub fn triangle_at(
+        &self,
+        voronoi: &Voronoi,
+        sphere: Option<&Sphere>,
+        x: f64,
+        y: f64,
+    ) -> Option<usize> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let Voronoi { delaunay, .. } = voronoi;
+        let q = self.query(sphere, x, y);
+        let start = *self
+            .starts
+            .get(self.grid_cell(x, y))
+            .filter(|&&p| p != EMPTY)?;
+        let mut t = delaunay.inedges[start] / 3;
+
+        // Step over any edge with `q` on the far side from the rest of the triangle. Give up
+        // rather than go round in circles on degenerate triangles.
+        'walk: for _ in 0..delaunay.triangles.len() / 3 {
+            for e in t * 3..t * 3 + 3 {
+                let corner = |e: usize| self.point(voronoi, sphere, delaunay.triangles[e], &q);
+                let (a, b) = (corner(e), corner(next_halfedge(e)));
+                let c = corner(next_halfedge(next_halfedge(e)));
+                if det(&a, &b, &q) * det(&a, &b, &c) < 0. {
+                    match delaunay.halfedges[e] {
+                        EMPTY => return None,
+                        twin => {
+                            t = twin / 3;
+                            continue 'walk;
+                        }
+                    }
+                }
+            }
+            return Some(t);
+        }
+        None
+    }
+
+    // The point whose cell `(x, y)` is in, `None` off the map.
+    pub fn cell_at(
+        &self,
+        voronoi: &Voronoi,
+        sphere: Option<&Sphere>,
+        x: f64,
+        y: f64,
+    ) -> Option<usize> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let Voronoi { delaunay, .. } = voronoi;
+        let q = self.query(sphere, x, y);
+        let distance = |p: usize| {
+            let p = self.point(voronoi, sphere, p, &q);
+            (0..3).map(|axis| (p[axis] - q[axis]).powi(2)).sum::<f64>()
+        };
+
+        let mut nearest = match self.triangle_at(voronoi, sphere, x, y) {
+            Some(t) => *delaunay.triangles[t * 3..t * 3 + 3]
+                .iter()
+                .min_by(|&&a, &&b| distance(a).total_cmp(&distance(b)))?,
+            None => *self
+                .starts
+                .get(self.grid_cell(x, y))
+                .filter(|&&p| p != EMPTY)?,
+        };
+        // The nearest point is always reachable by stepping to ever nearer neighbours.
+        loop {
+            let next = delaunay.neighbors[nearest]
+                .iter()
+                .copied()
+                .min_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+            match next {
+                Some(next) if distance(next) < distance(nearest) => nearest = next,
+                _ => return Some(nearest),
+            }
+        }
+    }
+
+    // Interpolated across the `voronoi_triangles` around the cell `(x, y)` is in, from the cell's
+    // height at its point and the corner heights. Cells open to the outside of the hull are flat
+    // where their fan doesn't reach.
+    pub fn height_at(
+        &self,
+        voronoi: &Voronoi,
+        sphere: Option<&Sphere>,
+        heights: &[f64],
+        cell_heights: &[f64],
+        x: f64,
+        y: f64,
+    ) -> Option<f64> {
+        let cell = self.cell_at(voronoi, sphere, x, y)?;
+        let q = self.query(sphere, x, y);
+
+        // Fans don't quite follow the cells unless corners are circumcenters, so look next door
+        // as well.
+        let cells = std::iter::once(&cell).chain(voronoi.delaunay.neighbors[cell].iter());
+        for &p in cells {
+            for fan in
+                voronoi.voronoi_triangles[self.fans[p] * 3..self.fans[p + 1] * 3].chunks_exact(3)
+            {
+                let a = self.point(voronoi, sphere, fan[0], &q);
+                let b = self.corner(voronoi, sphere, fan[1], &q);
+                let c = self.corner(voronoi, sphere, fan[2], &q);
+                if let Some([wa, wb, wc]) = barycentric(&a, &b, &c, &q) {
+                    return Some(
+                        wa * cell_heights[fan[0]] + wb * heights[fan[1]] + wc * heights[fan[2]],
+                    );
+                }
+            }
+        }
+        cell_heights.get(cell).copied()
+    }
+}
+
+fn position(positions: &[f64], i: usize) -> [f64; 3] {
+    [positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]]
+}
+
+fn det(a: &[f64; 3], b: &[f64; 3], c: &[f64; 3]) -> f64 {
+    sphere::dot(&sphere::cross(a, b), c)
+}
+
+// Weights of `a`, `b` and `c` at `q`, if `q` is inside the triangle.
+fn barycentric(a: &[f64; 3], b: &[f64; 3], c: &[f64; 3], q: &[f64; 3]) -> Option<[f64; 3]> {
+    let weights = [det(q, b, c), det(a, q, c), det(a, b, q)];
+    let total = weights.iter().sum::<f64>();
+    if total == 0. || !total.is_finite() {
+        return None;
+    }
+    let weights = weights.map(|w| w / total);
+    let epsilon = 1e-9;
+    if weights.iter().all(|&w| w >= -epsilon) {
+        Some(weights)
+    } else {
+        None
+    }
+}
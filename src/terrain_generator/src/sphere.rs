@@ -0,0 +1,178 @@
+use bracket_random::prelude::RandomNumberGenerator;
+use std::f64::consts::PI;
+
+use super::error::TerrainError;
+use super::voronoi::VertexMode;
+
+// A planet is the unit sphere. The 2D `points` and `circumcenters` of a planet's `Voronoi` are
+// an equirectangular map of it, north up, 2 units wide and 1 unit high.
+pub const MAP_BOUNDS: [f64; 4] = [0., 0., 2., 1.];
+
+// Positions on the unit sphere, three floats per point.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Sphere {
+    // Per point (cell).
+    pub positions: Vec<f64>,
+
+    // Per voronoi corner, the same order as `Voronoi::circumcenters`.
+    #[serde(rename = "circumcenterPositions")]
+    pub circumcenters: Vec<f64>,
+
+    // Per point, `[latitude, longitude]` in degrees.
+    #[serde(rename = "latLon")]
+    pub lat_lon: Vec<f64>,
+}
+
+impl Sphere {
+    pub fn new(positions: Vec<f64>) -> Sphere {
+        let lat_lon = positions
+            .chunks_exact(3)
+            .flat_map(|p| {
+                let (lat, lon) = lat_lon(p);
+                vec![lat, lon]
+            })
+            .collect();
+        Sphere {
+            positions,
+            circumcenters: Vec::new(),
+            lat_lon,
+        }
+    }
+}
+
+// Points spread evenly over the sphere along a Fibonacci spiral, nudged a little by `rng` so the
+// cells don't all line up. `radius` is the spacing, as for the flat Poisson sampler.
+pub fn fibonacci_points(
+    radius: f64,
+    rng: &mut RandomNumberGenerator,
+) -> Result<Vec<f64>, TerrainError> {
+    // The same number of points per unit of area `terrain.js` asks of a flat map.
+    let n = 4. * PI * 5. / radius.powi(2);
+    if !(n.is_finite() && n >= 4.) {
+        return Err(TerrainError::InvalidRadius(radius));
+    }
+    let n = n as usize;
+    let golden_angle = PI * (3. - 5_f64.sqrt());
+    let jitter = 0.5 * (4. * PI / n as f64).sqrt();
+
+    let mut points = Vec::with_capacity(n * 3);
+    for i in 0..n {
+        let z = 1. - (2 * i + 1) as f64 / n as f64;
+        let r = (1. - z * z).sqrt();
+        let theta = golden_angle * i as f64;
+
+        let mut p = [libm::cos(theta) * r, libm::sin(theta) * r, z];
+        for x in p.iter_mut() {
+            *x += (rng.rand::<f64>() - 0.5) * jitter;
+        }
+        points.extend(normalize(p).iter());
+    }
+    Ok(points)
+}
+
+pub fn normalize(p: [f64; 3]) -> [f64; 3] {
+    let length = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+    [p[0] / length, p[1] / length, p[2] / length]
+}
+
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: &[f64], b: &[f64]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+// Great circle distance between two points on the unit sphere.
+pub fn arc(a: &[f64], b: &[f64]) -> f64 {
+    let c = cross(a, b);
+    libm::atan2(dot(&c, &c).sqrt(), dot(a, b))
+}
+
+// Latitude and longitude of `p`, in degrees.
+pub fn lat_lon(p: &[f64]) -> (f64, f64) {
+    let lat = libm::asin(p[2].clamp(-1., 1.)).to_degrees();
+    let lon = libm::atan2(p[1], p[0]).to_degrees();
+    (lat, lon)
+}
+
+// Where `p` ends up on the equirectangular map, see `MAP_BOUNDS`.
+pub fn map_position(p: &[f64]) -> (f64, f64) {
+    let (lat, lon) = lat_lon(p);
+    ((lon + 180.) / 180., (90. - lat) / 180.)
+}
+
+// The point on the sphere at `(x, y)` on the map, the other way round from `map_position`.
+pub fn from_map_position(x: f64, y: f64) -> [f64; 3] {
+    let (lat, lon) = (
+        (90. - y * 180.).to_radians(),
+        (x * 180. - 180.).to_radians(),
+    );
+    [
+        libm::cos(lat) * libm::cos(lon),
+        libm::cos(lat) * libm::sin(lon),
+        libm::sin(lat),
+    ]
+}
+
+// The corner of the voronoi cells between each triangle's points, see `VertexMode`. Each is found
+// on the flat triangle and pushed back out onto the sphere. Triangles must wind anticlockwise seen
+// from outside, as `Voronoi::new_spherical` leaves them.
+pub fn circumcenters(positions: &[f64], triangles: &[usize], mode: VertexMode) -> Vec<f64> {
+    let mut circumcenters = Vec::with_capacity(triangles.len());
+    for corners in triangles.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| {
+            let p = &positions[corners[i] * 3..corners[i] * 3 + 3];
+            [p[0], p[1], p[2]]
+        });
+        let vertex = match mode {
+            VertexMode::Centroid => add(add(a, b), c),
+            VertexMode::Circumcenter => circumcenter(a, b, c),
+            VertexMode::Incenter => {
+                let weigh = |p: [f64; 3], q: [f64; 3], r: [f64; 3]| scale(p, distance(q, r));
+                add(add(weigh(a, b, c), weigh(b, c, a)), weigh(c, a, b))
+            }
+            VertexMode::ClampedCircumcenter => {
+                let obtuse =
+                    |p: [f64; 3], q: [f64; 3], r: [f64; 3]| dot(&sub(q, p), &sub(r, p)) < 0.;
+                if obtuse(a, b, c) {
+                    add(b, c)
+                } else if obtuse(b, c, a) {
+                    add(c, a)
+                } else if obtuse(c, a, b) {
+                    add(a, b)
+                } else {
+                    circumcenter(a, b, c)
+                }
+            }
+        };
+        circumcenters.extend(normalize(vertex).iter());
+    }
+    circumcenters
+}
+
+// On the unit sphere, the circumcenter is straight out from the plane through the three points.
+fn circumcenter(a: [f64; 3], b: [f64; 3], c: [f64; 3]) -> [f64; 3] {
+    cross(&sub(b, a), &sub(c, a))
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f64; 3], s: f64) -> [f64; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let d = sub(a, b);
+    dot(&d, &d).sqrt()
+}
@@ -0,0 +1,930 @@
+use delaunator::EMPTY;
+use std::collections::BTreeMap;
+
+use super::bathymetry::Bathymetry;
+use super::circulation::{Circulation, Winds};
+use super::coasts::*;
+use super::derivatives::{Derivatives, Lighting, Nodes, Relief};
+use super::distance::{corner_lengths, distances, distances_from, point_lengths};
+use super::domain::Domain;
+use super::erosion::*;
+use super::error::TerrainError;
+use super::geometry::Geometry;
+use super::glaciation::Glaciation;
+use super::heightmap::apply_heightmap;
+use super::masks::apply_masks;
+use super::poisson;
+use super::relax;
+use super::rivers::*;
+use super::spatial::SpatialIndex;
+use super::sphere::{self, Sphere};
+use super::terrain_generator::{TerrainGenerator, World};
+use super::tile::{Tile, TileBoundary};
+use super::voronoi::Voronoi;
+
+// The world in progress, passed from stage to stage. Fields are empty until a stage fills them.
+// Custom stages can stash their own per-point or per-cell data in `layers`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct WorldContext {
+    pub radius: f64,
+    #[serde(rename = "seaLevel")]
+    pub sea_level: f64,
+    pub domain: Domain,
+
+    // Only set for a planet, see `planet_stages`.
+    pub sphere: Option<Sphere>,
+    // Only set for a tile of an endless map, see `tile_stages`.
+    pub tile: Option<Tile>,
+    pub points: Vec<f64>,
+    pub voronoi: Option<Voronoi>,
+    pub heights: Vec<f64>,
+    #[serde(rename = "cellHeights")]
+    pub cell_heights: Vec<f64>,
+    pub rivers: Vec<Vec<(usize, f64)>>,
+    #[serde(rename = "coastLines")]
+    pub coast_lines: Vec<(usize, usize)>,
+    pub geometry: Option<Geometry>,
+    pub relief: Option<Relief>,
+    pub circulation: Option<Circulation>,
+    pub layers: BTreeMap<String, Vec<f64>>,
+}
+
+impl WorldContext {
+    pub fn new(radius: f64, sea_level: f64, domain: Domain) -> WorldContext {
+        WorldContext {
+            radius,
+            sea_level,
+            domain,
+            sphere: None,
+            tile: None,
+            points: Vec::new(),
+            voronoi: None,
+            heights: Vec::new(),
+            cell_heights: Vec::new(),
+            rivers: Vec::new(),
+            coast_lines: Vec::new(),
+            geometry: None,
+            relief: None,
+            circulation: None,
+            layers: BTreeMap::new(),
+        }
+    }
+
+    // Fails if no stage has built the voronoi yet.
+    pub fn voronoi(&self) -> Result<&Voronoi, TerrainError> {
+        self.voronoi
+            .as_ref()
+            .ok_or(TerrainError::MissingStage("voronoi"))
+    }
+
+    // `[xmin, ymin, xmax, ymax]` of the map being generated.
+    pub fn bounds(&self) -> [f64; 4] {
+        match (&self.sphere, &self.tile) {
+            (Some(_), _) => sphere::MAP_BOUNDS,
+            (None, Some(tile)) => tile.bounds(&self.domain),
+            (None, None) => self.domain.bounds(),
+        }
+    }
+
+    // Latitude in degrees of each cell, or of each voronoi corner with `corners`. Planets have
+    // their own, a flat map runs from `north` along its top to `south` along its bottom.
+    pub fn latitudes(
+        &self,
+        corners: bool,
+        north: f64,
+        south: f64,
+    ) -> Result<Vec<f64>, TerrainError> {
+        let voronoi = self.voronoi()?;
+        let latitudes = match &self.sphere {
+            Some(sphere) if corners => sphere
+                .circumcenters
+                .chunks_exact(3)
+                .map(|p| sphere::lat_lon(p).0)
+                .collect(),
+            Some(sphere) => sphere.lat_lon.iter().step_by(2).copied().collect(),
+            None => {
+                let points = if corners {
+                    &voronoi.circumcenters
+                } else {
+                    &voronoi.delaunay.points
+                };
+                points
+                    .chunks_exact(2)
+                    .map(|p| (north + p[1] / self.domain.height * (south - north)).clamp(-90., 90.))
+                    .collect()
+            }
+        };
+        Ok(latitudes)
+    }
+
+    pub fn into_world(self) -> Result<World, TerrainError> {
+        let bounds = self.bounds();
+        let voronoi = self.voronoi.ok_or(TerrainError::MissingStage("voronoi"))?;
+        let index = SpatialIndex::new(&voronoi, bounds, self.domain.wrap && self.tile.is_none());
+        Ok(World {
+            voronoi,
+            heights: self.heights,
+            cell_heights: self.cell_heights,
+            rivers: self.rivers,
+            coast_lines: self.coast_lines,
+            geometry: self.geometry.unwrap_or_default(),
+            relief: self.relief,
+            circulation: self.circulation,
+            layers: self.layers,
+            sphere: self.sphere,
+            bounds,
+            index,
+        })
+    }
+}
+
+pub trait Stage {
+    fn name(&self) -> &str;
+
+    // How many times `run` is called for this stage. Each call is one step of a `Pipeline`.
+    fn iterations(&self) -> usize {
+        1
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        iteration: usize,
+    ) -> Result<(), TerrainError>;
+}
+
+pub fn default_stages() -> Vec<Box<dyn Stage>> {
+    vec![
+        Box::new(PoissonStage),
+        Box::new(VoronoiStage),
+        Box::new(NoiseStage),
+        Box::new(PlateauStage),
+        Box::new(ErosionStage { iterations: 10 }),
+        Box::new(CellHeightsStage),
+        Box::new(RiversStage),
+        Box::new(CoastsStage),
+        Box::new(GeometryStage),
+    ]
+}
+
+// A whole planet, see `sphere.rs`. There are no edges, so there is no plateau either.
+pub fn planet_stages() -> Vec<Box<dyn Stage>> {
+    vec![
+        Box::new(FibonacciStage),
+        Box::new(SphereVoronoiStage),
+        Box::new(NoiseStage),
+        Box::new(ErosionStage { iterations: 10 }),
+        Box::new(CellHeightsStage),
+        Box::new(RiversStage),
+        Box::new(CoastsStage),
+        Box::new(GeometryStage),
+    ]
+}
+
+// One tile of an endless map, see `tile.rs`. Tiles generated with the same `TerrainGenerator`
+// share their edges through its `boundary`, so they should be generated one after another from it.
+// Points, the mesh and the noise are the same whatever order tiles come in, but eroded heights
+// are not: a tile takes the heights along its edges from whichever neighbour came first, and
+// erodes the rest to match. Only heights cross from one tile into the next, rivers don't.
+pub fn tile_stages(tile: Tile) -> Vec<Box<dyn Stage>> {
+    vec![
+        Box::new(TilePointsStage { tile }),
+        Box::new(TileVoronoiStage),
+        Box::new(NoiseStage),
+        Box::new(TileErosionStage { iterations: 10 }),
+        Box::new(CellHeightsStage),
+        Box::new(RiversStage),
+        Box::new(CoastsStage),
+        Box::new(TileBoundaryStage),
+        Box::new(GeometryStage),
+    ]
+}
+
+pub struct PoissonStage;
+
+impl Stage for PoissonStage {
+    fn name(&self) -> &str {
+        "points"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        context.points = poisson::disc_sample(context.radius, context.sea_level, gen)?;
+        Ok(())
+    }
+}
+
+// Lloyd relaxation of the points, for more regular cells. Off unless `iterations` is above 0, see
+// `TerrainGenerator::stages`. With `weighted`, points stay denser where the density asks for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelaxStage {
+    pub iterations: usize,
+    pub strength: f64,
+    pub weighted: bool,
+}
+
+impl Default for RelaxStage {
+    fn default() -> RelaxStage {
+        RelaxStage {
+            iterations: 0,
+            strength: 1.,
+            weighted: true,
+        }
+    }
+}
+
+impl Stage for RelaxStage {
+    fn name(&self) -> &str {
+        "relax"
+    }
+
+    fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let sea_level = context.sea_level;
+        let gen = &*gen;
+        // Cells hold one point each, so their density goes with one over the spacing squared.
+        let density = |x: f64, y: f64| gen.density.spacing(x, y, sea_level, gen).powi(-2);
+        let weight: Option<&dyn Fn(f64, f64) -> f64> =
+            if self.weighted { Some(&density) } else { None };
+        context.points = relax::lloyd_step(
+            &context.points,
+            &context.domain,
+            self.strength,
+            weight,
+            &gen.progress,
+        )?;
+        Ok(())
+    }
+}
+
+pub struct VoronoiStage;
+
+impl Stage for VoronoiStage {
+    fn name(&self) -> &str {
+        "voronoi"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let points = std::mem::take(&mut context.points);
+        let mut voronoi = if context.domain.wrap {
+            Voronoi::new_wrapping(points, context.domain.width, gen.vertex_mode)?
+        } else {
+            Voronoi::new(points, gen.vertex_mode)?
+        };
+        voronoi.clip(context.domain.bounds(), context.domain.wrap);
+        context.voronoi = Some(voronoi);
+        Ok(())
+    }
+}
+
+pub struct FibonacciStage;
+
+impl Stage for FibonacciStage {
+    fn name(&self) -> &str {
+        "points"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let mut rng = gen.stage_rng("points");
+        let positions = sphere::fibonacci_points(context.radius, &mut rng)?;
+        context.sphere = Some(Sphere::new(positions));
+        Ok(())
+    }
+}
+
+pub struct SphereVoronoiStage;
+
+impl Stage for SphereVoronoiStage {
+    fn name(&self) -> &str {
+        "voronoi"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let sphere = context
+            .sphere
+            .as_mut()
+            .ok_or(TerrainError::MissingStage("fibonacci"))?;
+        let voronoi = Voronoi::new_spherical(&sphere.positions, gen.vertex_mode)?;
+        sphere.circumcenters = sphere::circumcenters(
+            &sphere.positions,
+            &voronoi.delaunay.triangles,
+            gen.vertex_mode,
+        );
+        context.voronoi = Some(voronoi);
+        Ok(())
+    }
+}
+
+// Samples the tile and the eight around it, so the mesh near the edges is the same as theirs.
+pub struct TilePointsStage {
+    pub tile: Tile,
+}
+
+impl Stage for TilePointsStage {
+    fn name(&self) -> &str {
+        "points"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let mut samples = Vec::new();
+        for tile in self.tile.neighborhood() {
+            let points = poisson::tile_sample(context.radius, context.sea_level, gen, &tile)?;
+            samples.push((tile, points));
+        }
+        context.points = poisson::merge_tiles(&samples, context.radius, context.sea_level, gen);
+        context.tile = Some(self.tile);
+        Ok(())
+    }
+}
+
+pub struct TileVoronoiStage;
+
+impl Stage for TileVoronoiStage {
+    fn name(&self) -> &str {
+        "voronoi"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let tile = context
+            .tile
+            .ok_or(TerrainError::MissingStage("tile points"))?;
+        let points = std::mem::take(&mut context.points);
+        let keep = points
+            .chunks_exact(2)
+            .map(|p| tile.contains(&context.domain, p[0], p[1]))
+            .collect::<Vec<_>>();
+        let mut voronoi = Voronoi::new_subset(points, &keep, gen.vertex_mode)?;
+        voronoi.clip(tile.bounds(&context.domain), false);
+        context.voronoi = Some(voronoi);
+        Ok(())
+    }
+}
+
+pub struct NoiseStage;
+
+impl Stage for NoiseStage {
+    fn name(&self) -> &str {
+        "heights"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let circumcenters = &context.voronoi()?.circumcenters;
+        // Heightmaps and masks are laid over the domain, which planets and endless maps don't stay
+        // inside.
+        context.heights = match (&context.sphere, &context.tile) {
+            (Some(sphere), _) => gen.noise_sphere(&sphere.circumcenters),
+            (None, Some(_)) => gen.noise_array(circumcenters, None),
+            (None, None) => {
+                let heights = gen.noise_array(circumcenters, None);
+                let heights = apply_heightmap(circumcenters, heights, gen);
+                apply_masks(circumcenters, heights, gen)
+            }
+        };
+        Ok(())
+    }
+}
+
+pub struct PlateauStage;
+
+impl Stage for PlateauStage {
+    fn name(&self) -> &str {
+        "plateau"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let heights = std::mem::take(&mut context.heights);
+        context.heights = plateau(&context.voronoi()?.circumcenters, heights, &context.domain);
+        Ok(())
+    }
+}
+
+pub struct ErosionStage {
+    pub iterations: usize,
+}
+
+impl Stage for ErosionStage {
+    fn name(&self) -> &str {
+        "erosion"
+    }
+
+    fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let heights = std::mem::take(&mut context.heights);
+        context.heights = erode(
+            heights,
+            &context.voronoi()?.adjacent,
+            context.sea_level,
+            &[],
+        )?;
+        Ok(())
+    }
+}
+
+// Erosion with the cut edges of the tile draining like the sea, and the corners already shared
+// with earlier tiles pinned to their heights.
+pub struct TileErosionStage {
+    pub iterations: usize,
+}
+
+impl Stage for TileErosionStage {
+    fn name(&self) -> &str {
+        "erosion"
+    }
+
+    fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let heights = std::mem::take(&mut context.heights);
+        let voronoi = context.voronoi()?;
+        let halfedges = &voronoi.delaunay.halfedges;
+        let drains = (0..halfedges.len())
+            .filter(|&e| halfedges[e] == EMPTY)
+            .map(|e| e / 3)
+            .collect::<Vec<_>>();
+
+        let mut heights = erode(heights, &voronoi.adjacent, context.sea_level, &drains)?;
+        pin_heights(&mut heights, &voronoi.circumcenters, &gen.boundary.borrow());
+        context.heights = heights;
+        Ok(())
+    }
+}
+
+// Shapes the sea floor after erosion, see `bathymetry.rs`, and keeps the depth below sea level of
+// each cell in the `"depth"` layer. Off unless set on the generator, see
+// `TerrainGenerator::stages`. Not for tiles, whose sea floors wouldn't meet.
+pub struct BathymetryStage {
+    pub settings: Bathymetry,
+}
+
+impl Stage for BathymetryStage {
+    fn name(&self) -> &str {
+        "bathymetry"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let mut heights = std::mem::take(&mut context.heights);
+        let mut rng = gen.stage_rng("bathymetry");
+        let voronoi = context.voronoi()?;
+        self.settings.shape(
+            &mut heights,
+            voronoi,
+            context.sea_level,
+            corner_lengths(context)?,
+            &mut rng,
+            &gen.progress,
+        )?;
+
+        let cell_heights = TerrainGenerator::get_cell_heights(
+            voronoi.delaunay.points.len() / 2,
+            &heights,
+            &voronoi.voronoi_points,
+        );
+        let depths = cell_heights
+            .iter()
+            .map(|&height| (context.sea_level - height).max(0.))
+            .collect();
+        context.layers.insert("depth".to_string(), depths);
+        context.heights = heights;
+        Ok(())
+    }
+}
+
+// Ice caps and glaciers, see `glaciation.rs`, carving U-shaped valleys and fjords after erosion.
+// Keeps how much of each cell's corners are under ice, from 0 to 1, in the `"ice"` layer. Off
+// unless set on the generator, see `TerrainGenerator::stages`.
+pub struct GlaciationStage {
+    pub settings: Glaciation,
+}
+
+impl Stage for GlaciationStage {
+    fn name(&self) -> &str {
+        "glaciation"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let mut heights = std::mem::take(&mut context.heights);
+        let voronoi = context.voronoi()?;
+        let latitudes = context.latitudes(true, self.settings.north, self.settings.south)?;
+        let [xmin, ymin, xmax, ymax] = context.bounds();
+        let area = match context.sphere {
+            Some(_) => 4. * std::f64::consts::PI,
+            None => (xmax - xmin) * (ymax - ymin),
+        } / heights.len().max(1) as f64;
+        let ice = self.settings.carve(
+            &mut heights,
+            &voronoi.adjacent,
+            &latitudes,
+            context.sea_level,
+            area,
+            corner_lengths(context)?,
+            &gen.progress,
+        )?;
+
+        let cover = voronoi
+            .voronoi_points
+            .iter()
+            .take(voronoi.delaunay.points.len() / 2)
+            .map(|corners| {
+                let iced = corners.iter().filter(|&&corner| ice[corner]).count();
+                iced as f64 / corners.len().max(1) as f64
+            })
+            .collect();
+        context.layers.insert("ice".to_string(), cover);
+        context.heights = heights;
+        Ok(())
+    }
+}
+
+fn pin_heights(heights: &mut [f64], circumcenters: &[f64], boundary: &TileBoundary) {
+    if boundary.is_empty() {
+        return;
+    }
+    for (i, height) in heights.iter_mut().enumerate() {
+        if let Some(pinned) = boundary.get(circumcenters[i * 2], circumcenters[i * 2 + 1]) {
+            *height = pinned;
+        }
+    }
+}
+
+// Stages that reshape the corner heights go before this one, see `TerrainGenerator::stages`.
+pub const CELL_HEIGHTS: &str = "cell heights";
+
+pub struct CellHeightsStage;
+
+impl Stage for CellHeightsStage {
+    fn name(&self) -> &str {
+        CELL_HEIGHTS
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let voronoi = context.voronoi()?;
+        context.cell_heights = TerrainGenerator::get_cell_heights(
+            voronoi.delaunay.points.len() / 2,
+            &context.heights,
+            &voronoi.voronoi_points,
+        );
+        Ok(())
+    }
+}
+
+pub struct RiversStage;
+
+impl Stage for RiversStage {
+    fn name(&self) -> &str {
+        "rivers"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let voronoi = context.voronoi()?;
+        context.rivers = get_rivers(
+            &context.heights,
+            &voronoi.adjacent,
+            context.sea_level,
+            &voronoi.voronoi_cells,
+            &context.cell_heights,
+        )?;
+        Ok(())
+    }
+}
+
+pub struct CoastsStage;
+
+impl Stage for CoastsStage {
+    fn name(&self) -> &str {
+        "coasts"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let voronoi = context.voronoi()?;
+        context.coast_lines = get_coast_lines(
+            &context.cell_heights,
+            &voronoi.delaunay.neighbors,
+            &voronoi.voronoi_points,
+            &voronoi.voronoi_cells,
+            context.sea_level,
+        );
+        Ok(())
+    }
+}
+
+pub struct GeometryStage;
+
+impl Stage for GeometryStage {
+    fn name(&self) -> &str {
+        "geometry"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let voronoi = context.voronoi()?;
+        let geometry = match &context.sphere {
+            Some(sphere) => Geometry::spherical(voronoi, sphere),
+            None => Geometry::new(voronoi, context.bounds(), context.domain.wrap),
+        };
+        context.geometry = Some(geometry);
+        Ok(())
+    }
+}
+
+// Slope, aspect, curvature and hillshade at every corner and cell, see `derivatives.rs`. Off
+// unless set on the generator, see `TerrainGenerator::stages`.
+pub struct DerivativesStage {
+    pub lighting: Lighting,
+}
+
+impl Stage for DerivativesStage {
+    fn name(&self) -> &str {
+        "derivatives"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let voronoi = context.voronoi()?;
+        let mut domain = context.domain;
+        domain.wrap = domain.wrap && context.tile.is_none();
+        let sphere = context.sphere.as_ref();
+
+        let corners = Nodes {
+            flat: &voronoi.circumcenters,
+            round: sphere.map(|sphere| &sphere.circumcenters[..]),
+            domain,
+        };
+        let cells = Nodes {
+            flat: &voronoi.delaunay.points,
+            round: sphere.map(|sphere| &sphere.positions[..]),
+            domain,
+        };
+        let relief = Relief {
+            corners: Derivatives::new(
+                &corners,
+                &voronoi.adjacent,
+                &context.heights,
+                &self.lighting,
+            ),
+            cells: Derivatives::new(
+                &cells,
+                &voronoi.delaunay.neighbors,
+                &context.cell_heights,
+                &self.lighting,
+            ),
+        };
+        context.relief = Some(relief);
+        Ok(())
+    }
+}
+
+// Prevailing winds and ocean currents per cell, see `circulation.rs`. Off unless set on the
+// generator, see `TerrainGenerator::stages`.
+pub struct CirculationStage {
+    pub winds: Winds,
+}
+
+impl Stage for CirculationStage {
+    fn name(&self) -> &str {
+        "circulation"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let voronoi = context.voronoi()?;
+        let mut domain = context.domain;
+        domain.wrap = domain.wrap && context.tile.is_none();
+        let cells = Nodes {
+            flat: &voronoi.delaunay.points,
+            round: context.sphere.as_ref().map(|sphere| &sphere.positions[..]),
+            domain,
+        };
+        let Winds { north, south, .. } = self.winds;
+        let latitudes = context.latitudes(false, north, south)?;
+        let circulation = Circulation::new(
+            &self.winds,
+            &cells,
+            &voronoi.delaunay.neighbors,
+            &context.cell_heights,
+            context.sea_level,
+            &latitudes,
+        );
+        context.circulation = Some(circulation);
+        Ok(())
+    }
+}
+
+// Distances from each cell to the coast (negative at sea), the nearest river and the nearest
+// mountain, through neighbouring cells, in the `"coastDistance"`, `"riverDistance"` and
+// `"mountainDistance"` layers. Mountains are the highest `mountains` fraction of the land. Cells
+// with nothing to reach are `f64::MAX` away (`-f64::MAX` at sea), which unlike infinity survives
+// the trip to JS. Off unless set on the generator, see `TerrainGenerator::stages`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceStage {
+    pub mountains: f64,
+}
+
+impl Default for DistanceStage {
+    fn default() -> DistanceStage {
+        DistanceStage { mountains: 0.05 }
+    }
+}
+
+impl Stage for DistanceStage {
+    fn name(&self) -> &str {
+        "distances"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let layers = {
+            let voronoi = context.voronoi()?;
+            let neighbors = &voronoi.delaunay.neighbors;
+            let heights = &context.cell_heights;
+            let length = point_lengths(context)?;
+            let is_land = |i: usize| heights[i] >= context.sea_level;
+
+            // The coast runs halfway between land and sea.
+            let mut coast = Vec::new();
+            for (i, cells) in neighbors.iter().enumerate() {
+                for &j in cells.iter().filter(|&&j| is_land(i) != is_land(j)) {
+                    coast.push((i, length(i, j) / 2.));
+                }
+            }
+            let coast = distances_from(neighbors, &coast, f64::INFINITY, &length, &gen.progress)?
+                .into_iter()
+                .enumerate()
+                .map(|(i, d)| if is_land(i) { d } else { -d })
+                .collect();
+
+            let rivers = context
+                .rivers
+                .iter()
+                .flatten()
+                .flat_map(|&(corner, _)| voronoi.voronoi_cells[corner].iter().copied())
+                .collect::<Vec<_>>();
+            let rivers = distances(neighbors, &rivers, f64::INFINITY, &length, &gen.progress)?;
+
+            let mut land = (0..heights.len())
+                .filter(|&i| is_land(i))
+                .collect::<Vec<_>>();
+            land.sort_by(|&a, &b| heights[b].total_cmp(&heights[a]));
+            let count = (land.len() as f64 * self.mountains.clamp(0., 1.)).ceil() as usize;
+            let mountains = distances(
+                neighbors,
+                &land[..count],
+                f64::INFINITY,
+                &length,
+                &gen.progress,
+            )?;
+
+            [
+                ("coastDistance", coast),
+                ("riverDistance", rivers),
+                ("mountainDistance", mountains),
+            ]
+        };
+        for (name, layer) in layers {
+            let layer = layer
+                .into_iter()
+                .map(|d: f64| d.clamp(-f64::MAX, f64::MAX))
+                .collect();
+            context.layers.insert(name.to_string(), layer);
+        }
+        Ok(())
+    }
+}
+
+// Hands the heights of every triangle that reaches outside the tile on to the tiles around it.
+pub struct TileBoundaryStage;
+
+impl Stage for TileBoundaryStage {
+    fn name(&self) -> &str {
+        "boundary"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        let tile = context
+            .tile
+            .ok_or(TerrainError::MissingStage("tile points"))?;
+        let voronoi = context.voronoi()?;
+        let points = &voronoi.delaunay.points;
+        let outside =
+            |&p: &usize| !tile.contains(&context.domain, points[p * 2], points[p * 2 + 1]);
+
+        for (t, corners) in voronoi.delaunay.triangles.chunks_exact(3).enumerate() {
+            if corners.iter().any(outside) {
+                let (x, y) = (
+                    voronoi.circumcenters[t * 2],
+                    voronoi.circumcenters[t * 2 + 1],
+                );
+                gen.boundary.borrow_mut().insert(x, y, context.heights[t]);
+            }
+        }
+        Ok(())
+    }
+}
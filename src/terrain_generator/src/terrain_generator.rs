@@ -1,12 +1,35 @@
+use bracket_random::prelude::RandomNumberGenerator;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
-use super::coasts::*;
-use super::erosion::*;
-use super::noise::Noise;
-use super::poisson;
-use super::rivers::*;
+use super::bathymetry::Bathymetry;
+use super::circulation::{Circulation, Winds};
+use super::contours::{contours, Contour};
+use super::density::Density;
+use super::derivatives::{Lighting, Relief};
+use super::domain::Domain;
+use super::erosion::plateau;
+use super::error::TerrainError;
+use super::geometry::Geometry;
+use super::glaciation::Glaciation;
+use super::heightmap::{apply_heightmap, Heightmap};
+use super::masks::{apply_masks, Mask, MaskLayer};
+use super::noise::{Noise, NoiseNode};
+use super::pipeline::{Pipeline, PipelineState};
+use super::progress::{CancellationToken, Progress};
+use super::raster::Raster;
+use super::seed::{derive_seed, StableHasher, DEFAULT_SEED};
+use super::spatial::SpatialIndex;
+use super::sphere::Sphere;
+use super::stage::{
+    default_stages, planet_stages, tile_stages, BathymetryStage, CirculationStage,
+    DerivativesStage, DistanceStage, GlaciationStage, RelaxStage, Stage, CELL_HEIGHTS,
+};
+use super::tile::{Tile, TileBoundary};
 use super::utils;
-use super::voronoi::Voronoi;
+use super::voronoi::{VertexMode, Voronoi};
 
 extern crate web_sys;
 
@@ -22,59 +45,537 @@ macro_rules! log {
 #[wasm_bindgen(readonly)]
 #[derive(Serialize, Debug, PartialEq)]
 pub struct World {
-    voronoi: Voronoi,
-    heights: Vec<f64>,
+    pub(crate) voronoi: Voronoi,
+    pub(crate) heights: Vec<f64>,
 
     #[serde(rename = "cellHeights")]
-    cell_heights: Vec<f64>,
-    rivers: Vec<Vec<(usize, f64)>>,
+    pub(crate) cell_heights: Vec<f64>,
+    pub(crate) rivers: Vec<Vec<(usize, f64)>>,
 
     #[serde(rename = "coastLines")]
-    coast_lines: Vec<(usize, usize)>,
+    pub(crate) coast_lines: Vec<(usize, usize)>,
+
+    // Cell areas, centroids, perimeters and shared edge lengths.
+    pub(crate) geometry: Geometry,
+
+    // Slope, aspect, curvature and hillshade, when asked for, see `DerivativesStage`.
+    pub(crate) relief: Option<Relief>,
+
+    // Winds and ocean currents, when asked for, see `CirculationStage`.
+    pub(crate) circulation: Option<Circulation>,
+
+    // `[xmin, ymin, xmax, ymax]` of the map, in world units.
+    pub(crate) bounds: [f64; 4],
+
+    // Extra per-point or per-cell data written by custom stages.
+    pub(crate) layers: BTreeMap<String, Vec<f64>>,
+
+    // 3D positions and latitude/longitude, for a planet.
+    pub(crate) sphere: Option<Sphere>,
+
+    // Only for looking things up, see `cell_at` and `height_at`.
+    #[serde(skip)]
+    pub(crate) index: SpatialIndex,
 }
 
 #[wasm_bindgen]
 impl World {
-    pub fn as_js_value(&self) -> JsValue {
-        JsValue::from_serde(&self).unwrap()
+    pub fn as_js_value(&self) -> Result<JsValue, TerrainError> {
+        to_js_value(&self)
+    }
+
+    // The point whose cell is at `(x, y)`, in world units. `undefined` off the map.
+    #[wasm_bindgen(js_name = "cellAt")]
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<usize> {
+        self.index
+            .cell_at(&self.voronoi, self.sphere.as_ref(), x, y)
+    }
+
+    // The height of the terrain at `(x, y)`, smoothly interpolated between the cell's point and
+    // the corners around it. `undefined` off the map.
+    #[wasm_bindgen(js_name = "heightAt")]
+    pub fn height_at(&self, x: f64, y: f64) -> Option<f64> {
+        self.index.height_at(
+            &self.voronoi,
+            self.sphere.as_ref(),
+            &self.heights,
+            &self.cell_heights,
+            x,
+            y,
+        )
+    }
+
+    // Contour lines every `interval` up and down from `base`, usually the sea level, with every
+    // `indexEvery`th one flagged as an index contour, see `contours.rs`.
+    #[wasm_bindgen(js_name = "contours")]
+    pub fn contours_js(
+        &self,
+        base: f64,
+        interval: f64,
+        index_every: usize,
+    ) -> Result<JsValue, TerrainError> {
+        to_js_value(&self.contours(base, interval, index_every)?)
+    }
+
+    // Stable across platforms, see `seed.rs`. Compare against the golden hashes in the tests.
+    pub fn hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        let write_floats = |hasher: &mut StableHasher, floats: &[f64]| {
+            hasher.write_usize(floats.len());
+            floats.iter().for_each(|&x| hasher.write_f64(x));
+        };
+        let write_indices = |hasher: &mut StableHasher, indices: &[usize]| {
+            hasher.write_usize(indices.len());
+            indices.iter().for_each(|&i| hasher.write_usize(i));
+        };
+
+        write_floats(&mut hasher, &self.bounds);
+        let voronoi = &self.voronoi;
+        write_floats(&mut hasher, &voronoi.delaunay.points);
+        write_indices(&mut hasher, &voronoi.delaunay.triangles);
+        write_floats(&mut hasher, &voronoi.circumcenters);
+        write_floats(&mut hasher, &self.heights);
+        write_floats(&mut hasher, &self.cell_heights);
+
+        hasher.write_usize(self.rivers.len());
+        for river in self.rivers.iter() {
+            hasher.write_usize(river.len());
+            for &(i, flux) in river.iter() {
+                hasher.write_usize(i);
+                hasher.write_f64(flux);
+            }
+        }
+
+        hasher.write_usize(self.coast_lines.len());
+        for &(a, b) in self.coast_lines.iter() {
+            hasher.write_usize(a);
+            hasher.write_usize(b);
+        }
+
+        hasher.write_usize(self.layers.len());
+        for (name, layer) in self.layers.iter() {
+            hasher.write_str(name);
+            write_floats(&mut hasher, layer);
+        }
+
+        if let Some(sphere) = &self.sphere {
+            write_floats(&mut hasher, &sphere.positions);
+        }
+
+        hasher.finish()
+    }
+}
+
+impl World {
+    pub fn voronoi(&self) -> &Voronoi {
+        &self.voronoi
+    }
+
+    pub fn cell_heights(&self) -> &[f64] {
+        &self.cell_heights
+    }
+
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    pub fn coast_lines(&self) -> &[(usize, usize)] {
+        &self.coast_lines
+    }
+
+    pub fn layers(&self) -> &BTreeMap<String, Vec<f64>> {
+        &self.layers
+    }
+
+    pub fn relief(&self) -> Option<&Relief> {
+        self.relief.as_ref()
+    }
+
+    pub fn circulation(&self) -> Option<&Circulation> {
+        self.circulation.as_ref()
+    }
+
+    pub fn contours(
+        &self,
+        base: f64,
+        interval: f64,
+        index_every: usize,
+    ) -> Result<Vec<Contour>, TerrainError> {
+        contours(self, base, interval, index_every)
     }
 }
 
+// Plain objects for maps too, the same shape `JSON.parse` would give.
+pub(crate) fn to_js_value<T: serde::Serialize>(value: &T) -> Result<JsValue, TerrainError> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| TerrainError::Serialization(e.to_string()))
+}
+
+// Clones share the progress callback, the cancellation token and the edges of the tiles generated
+// so far, see `pipeline`.
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct TerrainGenerator {
+    seed: u64,
     #[wasm_bindgen(skip)]
     pub noise: Noise,
+    #[wasm_bindgen(skip)]
+    pub progress: Progress,
+    #[wasm_bindgen(skip)]
+    pub density: Density,
+    #[wasm_bindgen(skip)]
+    pub domain: Domain,
+    #[wasm_bindgen(skip)]
+    pub vertex_mode: VertexMode,
+    #[wasm_bindgen(skip)]
+    pub relaxation: RelaxStage,
+    // Applied to the noise in turn, see `masks.rs`.
+    #[wasm_bindgen(skip)]
+    pub masks: Vec<MaskLayer>,
+    // Used in place of the noise, see `heightmap.rs`.
+    #[wasm_bindgen(skip)]
+    pub heightmap: Option<Heightmap>,
+    // Sea floor shaping, see `bathymetry.rs`. Off when `None`.
+    #[wasm_bindgen(skip)]
+    pub bathymetry: Option<Bathymetry>,
+    // Ice caps, glaciers and fjords, see `glaciation.rs`. Off when `None`.
+    #[wasm_bindgen(skip)]
+    pub glaciation: Option<Glaciation>,
+    // Distance to the coast, rivers and mountains, see `DistanceStage`. Off when `None`.
+    #[wasm_bindgen(skip)]
+    pub distance_fields: Option<DistanceStage>,
+    // Slope, aspect, curvature and hillshade, see `DerivativesStage`. Off when `None`.
+    #[wasm_bindgen(skip)]
+    pub derivatives: Option<Lighting>,
+    // Winds and ocean currents, see `CirculationStage`. Off when `None`.
+    #[wasm_bindgen(skip)]
+    pub circulation: Option<Winds>,
+    // Edges of the tiles generated so far, see `tile`.
+    #[wasm_bindgen(skip)]
+    pub boundary: Rc<RefCell<TileBoundary>>,
 }
 
 #[wasm_bindgen]
 impl TerrainGenerator {
     #[wasm_bindgen(constructor)]
-    pub fn new(seed: Option<u32>) -> TerrainGenerator {
+    pub fn new(seed: Option<u64>) -> TerrainGenerator {
         if cfg![target = "wasm32-unknown-unknown"] {
             utils::set_panic_hook();
         }
 
-        let seed = match seed {
-            None => 123456 as u64,
-            Some(seed) => seed as u64,
-        };
+        let seed = seed.unwrap_or(DEFAULT_SEED);
 
         TerrainGenerator {
-            noise: Noise::new(seed),
+            seed,
+            noise: Noise::new(derive_seed(seed, "noise")),
+            progress: Progress::default(),
+            density: Density::default(),
+            domain: Domain::default(),
+            vertex_mode: VertexMode::default(),
+            relaxation: RelaxStage::default(),
+            masks: Vec::new(),
+            heightmap: None,
+            bathymetry: None,
+            glaciation: None,
+            distance_fields: None,
+            derivatives: None,
+            circulation: None,
+            boundary: Rc::default(),
         }
     }
 
+    // Called as `callback(stage, fraction)` while `world` runs. `world` is synchronous, so to
+    // cancel from elsewhere in JS, step a `pipeline` instead and yield between steps.
+    #[wasm_bindgen(js_name = "onProgress")]
+    pub fn on_progress_js(&mut self, callback: Option<js_sys::Function>) {
+        match callback {
+            None => self.progress.clear_callback(),
+            Some(callback) => self.progress.set_callback(move |stage, fraction| {
+                let _ = callback.call2(&JsValue::NULL, &stage.into(), &fraction.into());
+            }),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    #[wasm_bindgen(js_name = "cancellationToken")]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.progress.token()
+    }
+
+    #[wasm_bindgen(js_name = "setCancellationToken")]
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.progress.set_token(token);
+    }
+
+    // Generate a `width` × `height` map (in world units) instead of the unit square. Noise
+    // features are about `scale` world units across.
+    #[wasm_bindgen(js_name = "setDomain")]
+    pub fn set_domain(&mut self, width: f64, height: f64, scale: f64) -> Result<(), TerrainError> {
+        let wrap = self.domain.wrap;
+        self.domain = Domain {
+            wrap,
+            ..Domain::new(width, height, scale)?
+        };
+        Ok(())
+    }
+
+    // Join the east and west edges of the map.
+    #[wasm_bindgen(js_name = "setWrap")]
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.domain.wrap = wrap;
+    }
+
+    #[wasm_bindgen(js_name = "setVertexMode")]
+    pub fn set_vertex_mode(&mut self, mode: VertexMode) {
+        self.vertex_mode = mode;
+    }
+
+    // Lloyd relaxation after sampling, see `RelaxStage`. 0 iterations turns it off. Only
+    // `world()` relaxes, `tile()` and `planet()` don't.
+    #[wasm_bindgen(js_name = "setRelaxation")]
+    pub fn set_relaxation(&mut self, iterations: usize, strength: f64, weighted: bool) {
+        self.relaxation = RelaxStage {
+            iterations,
+            strength,
+            weighted,
+        };
+    }
+
+    // The height noise as a JSON `NoiseNode`, see `noise.rs`. Nothing goes back to the default.
+    #[wasm_bindgen(js_name = "setNoiseGraph")]
+    pub fn set_noise_graph_js(&mut self, json: Option<String>) -> Result<(), TerrainError> {
+        let graph = match json {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| TerrainError::InvalidNoiseGraph(e.to_string()))?,
+            None => NoiseNode::default(),
+        };
+        self.set_noise_graph(&graph)
+    }
+
+    // A JSON `Bathymetry`, see `bathymetry.rs`, where any setting left out keeps its default.
+    // `undefined` turns it off. `tile()` ignores it, tiles' sea floors wouldn't meet.
+    #[wasm_bindgen(js_name = "setBathymetry")]
+    pub fn set_bathymetry_js(&mut self, json: Option<String>) -> Result<(), TerrainError> {
+        self.bathymetry = match json {
+            Some(json) => {
+                let bathymetry: Bathymetry = serde_json::from_str(&json)
+                    .map_err(|e| TerrainError::InvalidBathymetry(e.to_string()))?;
+                bathymetry.validate()?;
+                Some(bathymetry)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    // A JSON `Glaciation`, see `glaciation.rs`, where any setting left out keeps its default.
+    // `undefined` turns it off. `tile()` ignores it.
+    #[wasm_bindgen(js_name = "setGlaciation")]
+    pub fn set_glaciation_js(&mut self, json: Option<String>) -> Result<(), TerrainError> {
+        self.glaciation = match json {
+            Some(json) => {
+                let glaciation: Glaciation = serde_json::from_str(&json)
+                    .map_err(|e| TerrainError::InvalidGlaciation(e.to_string()))?;
+                glaciation.validate()?;
+                Some(glaciation)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    // Per cell distance layers, see `DistanceStage`, with mountains being the highest `mountains`
+    // fraction of the land. `tile()` ignores it.
+    #[wasm_bindgen(js_name = "setDistanceFields")]
+    pub fn set_distance_fields(&mut self, enabled: bool, mountains: f64) {
+        self.distance_fields = if enabled {
+            Some(DistanceStage { mountains })
+        } else {
+            None
+        };
+    }
+
+    // Slope, aspect, curvature and hillshade on the world, see `Lighting` for the sun and
+    // `exaggeration`. `tile()` ignores it.
+    #[wasm_bindgen(js_name = "setDerivatives")]
+    pub fn set_derivatives(
+        &mut self,
+        enabled: bool,
+        azimuth: f64,
+        altitude: f64,
+        exaggeration: f64,
+    ) {
+        self.derivatives = if enabled {
+            Some(Lighting {
+                azimuth,
+                altitude,
+                exaggeration,
+            })
+        } else {
+            None
+        };
+    }
+
+    // Winds and ocean currents on the world, see `Winds` for the settings. `north` and `south`
+    // are the latitudes of the top and bottom of a flat map. `tile()` ignores it.
+    #[wasm_bindgen(js_name = "setCirculation")]
+    pub fn set_circulation(
+        &mut self,
+        enabled: bool,
+        north: f64,
+        south: f64,
+        terrain: f64,
+        iterations: usize,
+    ) {
+        self.circulation = if enabled {
+            Some(Winds {
+                north,
+                south,
+                terrain,
+                iterations,
+            })
+        } else {
+            None
+        };
+    }
+
+    // A JSON `Mask`, see `masks.rs`, with a `strength` from 0 to 1.
+    // Only `world()` uses masks, `tile()` and `planet()` ignore them.
+    #[wasm_bindgen(js_name = "addMask")]
+    pub fn add_mask_js(&mut self, json: &str, strength: f64) -> Result<(), TerrainError> {
+        let mask: Mask =
+            serde_json::from_str(json).map_err(|e| TerrainError::InvalidMask(e.to_string()))?;
+        mask.validate()?;
+        self.masks.push(MaskLayer { mask, strength });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = "addImageMask")]
+    pub fn add_image_mask(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: Vec<f64>,
+        strength: f64,
+    ) -> Result<(), TerrainError> {
+        let mask = Mask::Image(Raster::new(width, height, data)?);
+        self.masks.push(MaskLayer { mask, strength });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = "clearMasks")]
+    pub fn clear_masks(&mut self) {
+        self.masks.clear();
+    }
+
+    // A PNG heightmap, see `Raster::from_png`, keeping `noise` of the noise from 0 to 1.
+    // Only `world()` uses heightmaps, `tile()` and `planet()` ignore them.
+    #[wasm_bindgen(js_name = "importHeightmap")]
+    pub fn import_heightmap(&mut self, png: &[u8], noise: f64) -> Result<(), TerrainError> {
+        let raster = Raster::from_png(png)?;
+        self.heightmap = Some(Heightmap { raster, noise });
+        Ok(())
+    }
+
+    // A headerless heightmap of 8 or 16 bit samples, see `Raster::from_raw`.
+    #[wasm_bindgen(js_name = "importRawHeightmap")]
+    pub fn import_raw_heightmap(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: &[u8],
+        bits: u8,
+        noise: f64,
+    ) -> Result<(), TerrainError> {
+        let raster = Raster::from_raw(width, height, data, bits)?;
+        self.heightmap = Some(Heightmap { raster, noise });
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = "clearHeightmap")]
+    pub fn clear_heightmap(&mut self) {
+        self.heightmap = None;
+    }
+
+    #[wasm_bindgen(js_name = "densityUniform")]
+    pub fn density_uniform(&mut self) {
+        self.density = Density::Uniform;
+    }
+
+    #[wasm_bindgen(js_name = "densityNoise")]
+    pub fn density_noise(&mut self) {
+        self.density = Density::Noise;
+    }
+
+    #[wasm_bindgen(js_name = "densityRadial")]
+    pub fn density_radial(&mut self, x: f64, y: f64, radius: f64, min: f64) {
+        self.density = Density::Radial { x, y, radius, min };
+    }
+
+    #[wasm_bindgen(js_name = "densityMask")]
+    pub fn density_mask(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: Vec<f64>,
+    ) -> Result<(), TerrainError> {
+        self.density = Density::Mask(Raster::new(width, height, data)?);
+        Ok(())
+    }
+
+    // `callback(x, y)` returns the spacing at that point, see `Density`.
+    #[wasm_bindgen(js_name = "densityFunction")]
+    pub fn density_function(&mut self, callback: js_sys::Function) {
+        self.density = Density::Custom(Rc::new(move |x, y| {
+            callback
+                .call2(&JsValue::NULL, &x.into(), &y.into())
+                .ok()
+                .and_then(|spacing| spacing.as_f64())
+                .unwrap_or(1.)
+        }));
+    }
+
     pub fn noise_single(&self, x: f64, y: f64) -> f64 {
-        (self.noise.fractal_noise(x, y) + 1.) / 2.
+        let Domain {
+            width, scale, wrap, ..
+        } = self.domain;
+        let noise = if wrap {
+            self.noise
+                .sample_wrapped(x / scale, y / scale, width / scale)
+        } else {
+            self.noise.sample_flat(x / scale, y / scale)
+        };
+        (noise + 1.) / 2.
+    }
+
+    // Noise at points on the unit sphere, three floats per point.
+    pub fn noise_sphere(&self, positions: &[f64]) -> Vec<f64> {
+        let scale = self.domain.scale;
+        positions
+            .chunks_exact(3)
+            .map(|p| {
+                let noise = self
+                    .noise
+                    .sample_3d(p[0] / scale, p[1] / scale, p[2] / scale);
+                (noise + 1.) / 2.
+            })
+            .collect()
     }
 
     #[wasm_bindgen(js_name = "heightmap")]
     pub fn heightmap_js(&self, points: Vec<f64>, heights: Option<Vec<f64>>) -> Vec<f64> {
         let heights = self.noise_array(&points, heights);
-        plateau(&points, heights)
+        let heights = apply_heightmap(&points, heights, self);
+        let heights = apply_masks(&points, heights, self);
+        plateau(&points, heights, &self.domain)
     }
 
-    fn noise_array(&self, points: &Vec<f64>, heights: Option<Vec<f64>>) -> Vec<f64> {
+    pub(crate) fn noise_array(&self, points: &Vec<f64>, heights: Option<Vec<f64>>) -> Vec<f64> {
         let heights = match heights {
             None => vec![0.; points.len() / 2],
             Some(heights) => heights,
@@ -85,7 +586,7 @@ impl TerrainGenerator {
         heights.iter().enumerate().map(noise).collect()
     }
 
-    fn get_cell_heights(
+    pub(crate) fn get_cell_heights(
         n: usize,
         heights: &Vec<f64>,
         voronoi_points: &Vec<Vec<usize>>,
@@ -107,9 +608,9 @@ impl TerrainGenerator {
         let mut triangle_heights = vec![0.; voronoi_triangles.len() / 3];
         for i in 0..triangle_heights.len() {
             let j = i * 3;
-            let center_height = cell_heights[voronoi_triangles[j + 0] % cell_heights.len()];
-            let height1 = heights[voronoi_triangles[j + 1] % heights.len()];
-            let height2 = heights[voronoi_triangles[j + 2] % heights.len()];
+            let center_height = cell_heights[voronoi_triangles[j + 0]];
+            let height1 = heights[voronoi_triangles[j + 1]];
+            let height2 = heights[voronoi_triangles[j + 2]];
 
             let mut mean = (center_height + height1 + height2) / 3.;
 
@@ -133,62 +634,130 @@ impl TerrainGenerator {
         triangle_heights
     }
 
-    pub fn world(&mut self, radius: f64, sea_level: f64) -> World {
-        log!("`world` called");
-        let points = poisson::disc_sample(radius, sea_level, self);
-        log!(" ✓ points poissoned");
-        let voronoi = Voronoi::new(points);
-        log!(" ✓ voronoi triangulated");
-
-        let heights = self.noise_array(&voronoi.circumcenters, None);
-        log!(" ✓ heights noised");
-        let mut heights = plateau(&voronoi.circumcenters, heights);
-        log!(" ·  ✓ and plateaued");
-
-        for _ in 0..10 {
-            heights = erode(heights, &voronoi.adjacent, sea_level);
-        }
-
-        log!(" ·  ✓ and eroded ×10");
-        let cell_heights = TerrainGenerator::get_cell_heights(
-            voronoi.delaunay.points.len() / 2,
-            &heights,
-            &voronoi.voronoi_points,
-        );
-
-        // let triangle_heights = TerrainGenerator::get_triangle_heights(
-        //     &cell_heights,
-        //     &heights,
-        //     &voronoi.voronoi_triangles,
-        //     sea_level,
-        // );
-        // log!(" ✓ triangle heights calculated");
-
-        let rivers = get_rivers(
-            &heights,
-            &voronoi.adjacent,
-            sea_level,
-            &voronoi.voronoi_cells,
-            &cell_heights,
-        );
-        log!(" ✓ rivers flowed");
-
-        let coast_lines = get_coast_lines(
-            &cell_heights,
-            &voronoi.delaunay.neighbors,
-            &voronoi.voronoi_points,
-            &voronoi.voronoi_cells,
-            sea_level,
-        );
-        log!(" ✓ coasts lines carved");
-
-        World {
-            voronoi,
-            heights,
-            cell_heights,
-            rivers,
-            // triangle_heights,
-            coast_lines,
+    pub fn world(&mut self, radius: f64, sea_level: f64) -> Result<World, TerrainError> {
+        let stages = self.stages();
+        self.world_with_stages(radius, sea_level, stages)
+    }
+
+    // A whole planet instead of a flat map. `radius` is the spacing between points on the unit
+    // sphere. The domain's scale still sets the size of the noise features.
+    pub fn planet(&mut self, radius: f64, sea_level: f64) -> Result<World, TerrainError> {
+        let stages = self.planet_stages();
+        self.world_with_stages(radius, sea_level, stages)
+    }
+
+    // Tile (`x`, `y`) of an endless map made of domain-sized tiles. Heights match along the edges
+    // of tiles generated earlier by this generator, so they depend on which tiles came first, see
+    // `tile_stages`. Points and the mesh don't.
+    pub fn tile(
+        &mut self,
+        x: i32,
+        y: i32,
+        radius: f64,
+        sea_level: f64,
+    ) -> Result<World, TerrainError> {
+        self.world_with_stages(radius, sea_level, tile_stages(Tile::new(x, y)))
+    }
+
+    // `world`, one step at a time, see `Pipeline`. Settings changed on this generator afterwards
+    // don't reach the pipeline, but progress, cancelling and tile edges are shared with it.
+    pub fn pipeline(&self, radius: f64, sea_level: f64) -> Pipeline {
+        Pipeline::with_stages(self.clone(), radius, sea_level, self.stages())
+    }
+
+    // `planet`, one step at a time, as `pipeline`.
+    #[wasm_bindgen(js_name = "planetPipeline")]
+    pub fn planet_pipeline(&self, radius: f64, sea_level: f64) -> Pipeline {
+        Pipeline::with_stages(self.clone(), radius, sea_level, self.planet_stages())
+    }
+
+    // `tile`, one step at a time, as `pipeline`.
+    #[wasm_bindgen(js_name = "tilePipeline")]
+    pub fn tile_pipeline(&self, x: i32, y: i32, radius: f64, sea_level: f64) -> Pipeline {
+        let stages = tile_stages(Tile::new(x, y));
+        Pipeline::with_stages(self.clone(), radius, sea_level, stages)
+    }
+
+    // Forget the edges of earlier tiles, for when they are no longer needed.
+    #[wasm_bindgen(js_name = "clearBoundaries")]
+    pub fn clear_boundaries(&mut self) {
+        self.boundary.borrow_mut().clear();
+    }
+}
+
+impl TerrainGenerator {
+    pub fn set_noise_graph(&mut self, graph: &NoiseNode) -> Result<(), TerrainError> {
+        self.noise = Noise::from_graph(derive_seed(self.seed, "noise"), graph)?;
+        Ok(())
+    }
+
+    // `default_stages()`, with the relaxation, bathymetry, derivatives, circulation and distance
+    // fields set on this generator.
+    pub fn stages(&self) -> Vec<Box<dyn Stage>> {
+        let mut stages = default_stages();
+        if self.relaxation.iterations > 0 {
+            stages.insert(1, Box::new(self.relaxation));
+        }
+        self.add_optional_stages(&mut stages);
+        stages
+    }
+
+    // `planet_stages()`, with the optional stages set on this generator.
+    pub fn planet_stages(&self) -> Vec<Box<dyn Stage>> {
+        let mut stages = planet_stages();
+        self.add_optional_stages(&mut stages);
+        stages
+    }
+
+    fn add_optional_stages(&self, stages: &mut Vec<Box<dyn Stage>>) {
+        // Right after erosion, before cell heights are averaged from the corners. Glaciers carve
+        // into land after the sea floor has been shaped, so fjords keep their depth.
+        if let Some(settings) = self.bathymetry {
+            let k = stages
+                .iter()
+                .position(|stage| stage.name() == CELL_HEIGHTS)
+                .unwrap_or(stages.len());
+            stages.insert(k, Box::new(BathymetryStage { settings }));
+        }
+        if let Some(settings) = self.glaciation {
+            let k = stages
+                .iter()
+                .position(|stage| stage.name() == CELL_HEIGHTS)
+                .unwrap_or(stages.len());
+            stages.insert(k, Box::new(GlaciationStage { settings }));
+        }
+        if let Some(lighting) = self.derivatives {
+            stages.push(Box::new(DerivativesStage { lighting }));
         }
+        if let Some(winds) = self.circulation {
+            stages.push(Box::new(CirculationStage { winds }));
+        }
+        if let Some(distances) = self.distance_fields {
+            stages.push(Box::new(distances));
+        }
+    }
+
+    // Run `stages` in order. Start from `default_stages()` to insert custom stages between the
+    // built-in ones.
+    pub fn world_with_stages(
+        &mut self,
+        radius: f64,
+        sea_level: f64,
+        stages: Vec<Box<dyn Stage>>,
+    ) -> Result<World, TerrainError> {
+        log!("`world` called");
+        let pipeline = PipelineState::new(radius, sea_level, self.domain, stages);
+        let world = pipeline.into_world(self)?;
+        log!(" ✓ world generated");
+        Ok(world)
+    }
+
+    // A random number generator of its own for `stage`, so stages don't disturb each other.
+    pub fn stage_rng(&self, stage: &str) -> RandomNumberGenerator {
+        RandomNumberGenerator::seeded(derive_seed(self.seed, stage))
+    }
+
+    pub fn on_progress<F: FnMut(&str, f64) + 'static>(&mut self, callback: F) {
+        self.progress.set_callback(callback);
     }
 }
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use super::domain::Domain;
+
+// One tile of an endless map. Tile (`x`, `y`) covers
+// `[x * width, (x + 1) * width) × [y * height, (y + 1) * height)` of the domain's size, in world
+// coordinates, so noise lines up between tiles by itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Tile {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Tile {
+    pub fn new(x: i32, y: i32) -> Tile {
+        Tile { x, y }
+    }
+
+    // `[xmin, ymin, xmax, ymax]`
+    pub fn bounds(&self, domain: &Domain) -> [f64; 4] {
+        let x = self.x as f64 * domain.width;
+        let y = self.y as f64 * domain.height;
+        [x, y, x + domain.width, y + domain.height]
+    }
+
+    // Each point belongs to exactly one tile, the edges are half open.
+    pub fn contains(&self, domain: &Domain, x: f64, y: f64) -> bool {
+        let [xmin, ymin, xmax, ymax] = self.bounds(domain);
+        xmin <= x && x < xmax && ymin <= y && y < ymax
+    }
+
+    // The tile and the eight around it.
+    pub fn neighborhood(&self) -> Vec<Tile> {
+        let mut tiles = Vec::with_capacity(9);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                tiles.push(Tile::new(self.x + dx, self.y + dy));
+            }
+        }
+        tiles
+    }
+}
+
+// Heights along the edges of tiles that have already been generated, keyed by the position of
+// the voronoi corner. Triangles that straddle two tiles show up in both, and the second tile pins
+// their heights to what the first one eroded them to.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TileBoundary {
+    heights: BTreeMap<(i64, i64), f64>,
+}
+
+impl TileBoundary {
+    fn key(x: f64, y: f64) -> (i64, i64) {
+        ((x * 1e9).round() as i64, (y * 1e9).round() as i64)
+    }
+
+    pub fn get(&self, x: f64, y: f64) -> Option<f64> {
+        self.heights.get(&TileBoundary::key(x, y)).copied()
+    }
+
+    // The first height recorded for a corner wins, so neighbouring tiles always agree on it. Which
+    // tile that is depends on the order they were generated in.
+    pub fn insert(&mut self, x: f64, y: f64, height: f64) {
+        self.heights
+            .entry(TileBoundary::key(x, y))
+            .or_insert(height);
+    }
+
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heights.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.heights.clear();
+    }
+}
@@ -1,7 +1,10 @@
 use delaunator::{triangulate, Point, Triangulation, EMPTY};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
 
-#[path = "utils.rs"]
-mod utils;
+use super::error::TerrainError;
+use super::sphere;
+use super::utils;
 
 extern crate web_sys;
 
@@ -15,6 +18,19 @@ macro_rules! log {
 // Implement _init from here:
 // https://github.com/d3/d3-delaunay/blob/master/src/voronoi.js
 
+// Where the corner of the voronoi cells between the three points of a triangle goes. Only
+// `Circumcenter` gives true voronoi cells, the others trade that for rounder cells.
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VertexMode {
+    #[default]
+    Centroid,
+    Circumcenter,
+    Incenter,
+    // The circumcenter, moved onto the longest edge when it falls outside the triangle.
+    ClampedCircumcenter,
+}
+
 #[derive(Serialize, Debug, PartialEq)]
 pub struct Voronoi {
     pub circumcenters: Vec<f64>,
@@ -23,6 +39,8 @@ pub struct Voronoi {
     pub voronoi_triangles: Vec<usize>,
     pub voronoi_points: Vec<Vec<usize>>,
     pub voronoi_cells: Vec<Vec<usize>>,
+    // Closed outline of each point's cell, cut to the map, see `clip`. Empty until then.
+    pub cell_polygons: Vec<Vec<f64>>,
 }
 
 // #[wasm_bindgen]
@@ -48,24 +66,270 @@ impl Voronoi {
     // Adapted from:
     //     https://github.com/d3/d3-delaunay/blob/master/src/voronoi.js
     //     https://github.com/d3/d3-delaunay/blob/master/src/delaunay.js
-    pub fn new(points: Vec<f64> /*, xmin: f64, ymin: f64, xmax: f64, ymax: f64*/) -> Voronoi {
+    pub fn new(
+        points: Vec<f64>, /*, xmin: f64, ymin: f64, xmax: f64, ymax: f64*/
+        mode: VertexMode,
+    ) -> Result<Voronoi, TerrainError> {
         utils::set_panic_hook();
         let Triangulation {
             triangles,
             halfedges,
             hull,
-        } = Voronoi::triangulate(&points);
+        } = Voronoi::triangulate(&points)?;
+        let inedges = Voronoi::get_inedges(&points, &halfedges, &triangles);
+        if let Some(i) = inedges.iter().position(|&e| e == EMPTY) {
+            return Err(TerrainError::CoincidentPoint(i));
+        }
+        let neighbors = Voronoi::get_neighbors(&points, &inedges, &halfedges, &triangles);
+        let circumcenters = Voronoi::get_circumcenters(&points, &triangles, mode);
+
+        Voronoi::assemble(
+            points,
+            hull,
+            inedges,
+            halfedges,
+            triangles,
+            neighbors,
+            circumcenters,
+        )
+    }
+
+    // Voronoi of a map whose east and west edges meet at x = 0 and x = `width`. Points near either
+    // edge are copied across the seam before triangulating, and each triangle is kept once, with
+    // indices pointing back at the original points. Triangles and cells can straddle the seam.
+    pub fn new_wrapping(
+        points: Vec<f64>,
+        width: f64,
+        mode: VertexMode,
+    ) -> Result<Voronoi, TerrainError> {
+        utils::set_panic_hook();
+        let n = points.len() / 2;
+        let (min_y, max_y) = points
+            .chunks_exact(2)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+                (lo.min(p[1]), hi.max(p[1]))
+            });
+        // A few point spacings is plenty for the triangulation near the seam to be right.
+        let margin = (3. * (width * (max_y - min_y) / n.max(1) as f64).sqrt()).min(width / 2.);
+
+        let mut ghosts = points.clone();
+        let mut origin: Vec<usize> = (0..n).collect();
+        for i in 0..n {
+            let (x, y) = (points[2 * i], points[2 * i + 1]);
+            if x < margin {
+                ghosts.extend([x + width, y].iter());
+                origin.push(i);
+            }
+            if x > width - margin {
+                ghosts.extend([x - width, y].iter());
+                origin.push(i);
+            }
+        }
+        let ghost_triangulation = Voronoi::triangulate(&ghosts)?;
+        let ghost_centers =
+            Voronoi::get_circumcenters(&ghosts, &ghost_triangulation.triangles, mode);
+
+        let mut triangles = Vec::new();
+        let mut circumcenters = Vec::new();
+        for (t, corners) in ghost_triangulation.triangles.chunks_exact(3).enumerate() {
+            let centroid_x = corners.iter().map(|&p| ghosts[2 * p]).sum::<f64>() / 3.;
+            if (0. ..width).contains(&centroid_x) {
+                triangles.extend(corners.iter().map(|&p| origin[p]));
+                circumcenters.push(ghost_centers[2 * t].rem_euclid(width));
+                circumcenters.push(ghost_centers[2 * t + 1]);
+            }
+        }
+
+        // Pair up the halfedges again, now that ghosts and originals share indices.
+        let halfedges = pair_halfedges(&triangles);
+        // Without a straight row along the top and bottom, as the sampler lays down, the hull can
+        // stretch long triangles past the copies, and the two sides of the seam disagree about
+        // them. Some edge then belongs to two triangles, and its twins don't pair up.
+        if (0..halfedges.len()).any(|e| halfedges[e] != EMPTY && halfedges[halfedges[e]] != e) {
+            return Err(TerrainError::Triangulation);
+        }
+
+        let inedges = Voronoi::get_inedges(&points, &halfedges, &triangles);
+        if let Some(i) = inedges.iter().position(|&e| e == EMPTY) {
+            return Err(TerrainError::CoincidentPoint(i));
+        }
+        // The north and south edges, which are all that is left of the hull.
+        let hull = (0..triangles.len())
+            .filter(|&e| halfedges[e] == EMPTY)
+            .map(|e| triangles[e])
+            .collect();
+        let neighbors = Voronoi::get_neighbors(&points, &inedges, &halfedges, &triangles);
+
+        Voronoi::assemble(
+            points,
+            hull,
+            inedges,
+            halfedges,
+            triangles,
+            neighbors,
+            circumcenters,
+        )
+    }
+
+    // Voronoi of points on the unit sphere, three floats per point. The sphere is projected
+    // stereographically from its last point, which keeps the Delaunay triangulation intact. The
+    // hull of the projection is then closed up with a fan of triangles around that last point, so
+    // every halfedge has a twin and there is no hull at all.
+    // `points` and `circumcenters` come out as a map, see `sphere::map_position`.
+    pub fn new_spherical(positions: &[f64], mode: VertexMode) -> Result<Voronoi, TerrainError> {
+        utils::set_panic_hook();
+        let n = positions.len() / 3;
+        if n < 4 {
+            return Err(TerrainError::Triangulation);
+        }
+        let pole = &positions[(n - 1) * 3..];
+        let u = sphere::normalize(sphere::cross(pole, &[pole[1], pole[2], pole[0]]));
+        let v = sphere::cross(pole, &u);
+
+        let mut projected = Vec::with_capacity((n - 1) * 2);
+        for p in positions[..(n - 1) * 3].chunks_exact(3) {
+            let d = 1. - sphere::dot(p, pole);
+            if d <= 0. {
+                return Err(TerrainError::CoincidentPoint(n - 1));
+            }
+            projected.push(sphere::dot(p, &u) / d);
+            projected.push(sphere::dot(p, &v) / d);
+        }
+        let Triangulation {
+            mut triangles,
+            halfedges,
+            ..
+        } = Voronoi::triangulate(&projected)?;
+        for e in 0..halfedges.len() {
+            if halfedges[e] == EMPTY {
+                triangles.extend([triangles[next_halfedge(e)], triangles[e], n - 1].iter());
+            }
+        }
+
+        // Wind every triangle anticlockwise seen from outside the sphere.
+        let corner = |p: usize| &positions[p * 3..p * 3 + 3];
+        let (a, b, c) = (
+            corner(triangles[0]),
+            corner(triangles[1]),
+            corner(triangles[2]),
+        );
+        if sphere::dot(a, &sphere::cross(b, c)) < 0. {
+            for t in triangles.chunks_exact_mut(3) {
+                t.swap(1, 2);
+            }
+        }
+        let halfedges = pair_halfedges(&triangles);
+
+        let mut points = Vec::with_capacity(n * 2);
+        for p in positions.chunks_exact(3) {
+            let (x, y) = sphere::map_position(p);
+            points.extend([x, y].iter());
+        }
+        let mut circumcenters = Vec::with_capacity(triangles.len() / 3 * 2);
+        for p in sphere::circumcenters(positions, &triangles, mode).chunks_exact(3) {
+            let (x, y) = sphere::map_position(p);
+            circumcenters.extend([x, y].iter());
+        }
+
+        let inedges = Voronoi::get_inedges(&points, &halfedges, &triangles);
+        if let Some(i) = inedges.iter().position(|&e| e == EMPTY) {
+            return Err(TerrainError::CoincidentPoint(i));
+        }
+        let neighbors = Voronoi::get_neighbors(&points, &inedges, &halfedges, &triangles);
+
+        Voronoi::assemble(
+            points,
+            Vec::new(),
+            inedges,
+            halfedges,
+            triangles,
+            neighbors,
+            circumcenters,
+        )
+    }
+
+    // Voronoi of the triangles that touch a point marked in `keep`, for a tile cut out of a larger
+    // set of points. Only points used by those triangles are kept, in their original order.
+    // Triangles start at their lowest point, so the same triangle in two overlapping tiles gets
+    // exactly the same circumcenter.
+    pub fn new_subset(
+        points: Vec<f64>,
+        keep: &[bool],
+        mode: VertexMode,
+    ) -> Result<Voronoi, TerrainError> {
+        utils::set_panic_hook();
+        let all_triangles = Voronoi::triangulate(&points)?.triangles;
+
+        let lowest = |t: &[usize]| {
+            let point = |i: usize| (points[t[i] * 2], points[t[i] * 2 + 1]);
+            (0..3)
+                .min_by(|&a, &b| {
+                    let (a, b) = (point(a), point(b));
+                    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+                })
+                .unwrap_or(0)
+        };
+        let mut index = vec![EMPTY; points.len() / 2];
+        let mut triangles = Vec::new();
+        for t in all_triangles.chunks_exact(3) {
+            if t.iter().any(|&p| keep[p]) {
+                let first = lowest(t);
+                for k in 0..3 {
+                    let p = t[(first + k) % 3];
+                    index[p] = 0;
+                    triangles.push(p);
+                }
+            }
+        }
+
+        let mut subset = Vec::new();
+        for (p, i) in index.iter_mut().enumerate() {
+            if *i != EMPTY {
+                *i = subset.len() / 2;
+                subset.extend(points[p * 2..p * 2 + 2].iter());
+            }
+        }
+        let points = subset;
+        triangles.iter_mut().for_each(|p| *p = index[*p]);
+
+        let halfedges = pair_halfedges(&triangles);
         let inedges = Voronoi::get_inedges(&points, &halfedges, &triangles);
-        let neighbors = Voronoi::get_neighbors(&points, &inedges, &hull, &halfedges, &triangles);
-        let circumcenters = Voronoi::get_circumcenters(&points, &triangles);
+        if let Some(i) = inedges.iter().position(|&e| e == EMPTY) {
+            return Err(TerrainError::CoincidentPoint(i));
+        }
+        let hull = (0..triangles.len())
+            .filter(|&e| halfedges[e] == EMPTY)
+            .map(|e| triangles[e])
+            .collect();
+        let neighbors = Voronoi::get_neighbors(&points, &inedges, &halfedges, &triangles);
+        let circumcenters = Voronoi::get_circumcenters(&points, &triangles, mode);
+
+        Voronoi::assemble(
+            points,
+            hull,
+            inedges,
+            halfedges,
+            triangles,
+            neighbors,
+            circumcenters,
+        )
+    }
 
+    fn assemble(
+        points: Vec<f64>,
+        hull: Vec<usize>,
+        inedges: Vec<usize>,
+        halfedges: Vec<usize>,
+        triangles: Vec<usize>,
+        neighbors: Vec<Vec<usize>>,
+        circumcenters: Vec<f64>,
+    ) -> Result<Voronoi, TerrainError> {
         let Adjacencies {
             adjacent,
             voronoi_triangles,
             voronoi_points,
             voronoi_cells,
-        } = Voronoi::get_adjacencies(&points, &circumcenters, &inedges, &halfedges, &triangles)
-            .unwrap();
+        } = Voronoi::get_adjacencies(&points, &circumcenters, &inedges, &halfedges, &triangles)?;
 
         let delaunay = Delaunay {
             points,
@@ -76,24 +340,110 @@ impl Voronoi {
             neighbors,
         };
 
-        Voronoi {
+        Ok(Voronoi {
             circumcenters,
             delaunay,
             adjacent,
             voronoi_triangles,
             voronoi_points,
             voronoi_cells,
+            cell_polygons: Vec::new(),
+        })
+    }
+
+    // Close every cell and cut it to `bounds` (`[xmin, ymin, xmax, ymax]`), like d3-delaunay's
+    // `clip`. Cells on the hull are open to the outside, so they are closed with a ray out
+    // through each of the hull edges either side of them, which the cut then turns into corners
+    // on the edge of the map. On a wrapping map cells are only cut at the top and bottom, and
+    // reach past the seam when they straddle it. Cells entirely off the map come out empty.
+    pub fn clip(&mut self, bounds: [f64; 4], wrap: bool) {
+        let [xmin, ymin, xmax, ymax] = bounds;
+        let width = xmax - xmin;
+        // Far enough out that the end of a ray is always off the map.
+        let far = 1e6 * (width + ymax - ymin);
+        let period = if wrap { Some(width) } else { None };
+
+        let points = &self.delaunay.points;
+        let mut cell_polygons = Vec::with_capacity(points.len() / 2);
+        for i in 0..points.len() / 2 {
+            let site = (points[i * 2], points[i * 2 + 1]);
+            let mut polygon = self.voronoi_points[i]
+                .iter()
+                .map(|&t| {
+                    let (mut x, y) = (self.circumcenters[t * 2], self.circumcenters[t * 2 + 1]);
+                    if wrap {
+                        x -= ((x - site.0) / width).round() * width;
+                    }
+                    (x, y)
+                })
+                .collect::<Vec<_>>();
+
+            if let Some((ray_in, ray_out)) = self.hull_rays(i, period) {
+                let (first, last) = (polygon[0], polygon[polygon.len() - 1]);
+                polygon.insert(0, (first.0 + ray_in.0 * far, first.1 + ray_in.1 * far));
+                polygon.push((last.0 + ray_out.0 * far, last.1 + ray_out.1 * far));
+            }
+
+            let polygon = clip_polygon(polygon, bounds, wrap);
+            cell_polygons.push(polygon.iter().flat_map(|&(x, y)| vec![x, y]).collect());
         }
+        self.cell_polygons = cell_polygons;
     }
 
-    fn triangulate(points: &Vec<f64>) -> Triangulation {
+    // Outward directions through the hull edges coming into and going out of point `i`, or `None`
+    // if `i` is not on the hull. `width` unwraps the edges as in `hull_normal`.
+    fn hull_rays(&self, i: usize, width: Option<f64>) -> Option<((f64, f64), (f64, f64))> {
+        let Delaunay {
+            inedges,
+            halfedges,
+            triangles,
+            ..
+        } = &self.delaunay;
+        let e_in = inedges[i];
+        if halfedges[e_in] != EMPTY {
+            return None;
+        }
+        let last = *self.voronoi_points[i].last()?;
+        let e_out = (last * 3..last * 3 + 3).find(|&e| triangles[e] == i)?;
+
+        Some((
+            self.hull_normal(e_in, width),
+            self.hull_normal(e_out, width),
+        ))
+    }
+
+    // Perpendicular to the edge `e`, on the side away from the rest of its triangle. With `width`,
+    // the corners are first unwrapped next to the start of the edge.
+    pub(crate) fn hull_normal(&self, e: usize, width: Option<f64>) -> (f64, f64) {
+        let Delaunay {
+            points, triangles, ..
+        } = &self.delaunay;
+        let a = (points[triangles[e] * 2], points[triangles[e] * 2 + 1]);
+        let point = |p: usize| {
+            let (mut x, y) = (points[p * 2], points[p * 2 + 1]);
+            if let Some(width) = width {
+                x -= ((x - a.0) / width).round() * width;
+            }
+            (x, y)
+        };
+        let b = point(triangles[next_halfedge(e)]);
+        let c = point(triangles[next_halfedge(next_halfedge(e))]);
+        let (nx, ny) = (a.1 - b.1, b.0 - a.0);
+        if nx * (c.0 - a.0) + ny * (c.1 - a.1) > 0. {
+            (-nx, -ny)
+        } else {
+            (nx, ny)
+        }
+    }
+
+    fn triangulate(points: &Vec<f64>) -> Result<Triangulation, TerrainError> {
         let struct_points: Vec<&[f64]> = points.chunks_exact(2).collect();
         let struct_points = struct_points
             .iter()
             .map(|p| Point { x: p[0], y: p[1] })
             .collect::<Vec<_>>();
 
-        triangulate(&struct_points).unwrap()
+        triangulate(&struct_points).ok_or(TerrainError::Triangulation)
     }
 
     fn get_inedges(
@@ -111,7 +461,7 @@ impl Voronoi {
         inedges
     }
 
-    fn get_circumcenters(points: &Vec<f64>, triangles: &Vec<usize>) -> Vec<f64> {
+    fn get_circumcenters(points: &[f64], triangles: &[usize], mode: VertexMode) -> Vec<f64> {
         let n = triangles.len();
         let mut circumcenters = vec![0.0; n / 3 * 2];
         let mut i = 0;
@@ -135,8 +485,6 @@ impl Voronoi {
             let dy = y2 - y1;
             let ex = x3 - x1;
             let ey = y3 - y1;
-            // let bl = dx * dx + dy * dy;
-            // let cl = ex * ex + ey * ey;
             let ab = (dx * ey - dy * ex) * 2.0;
 
             if ab == 0.0 {
@@ -148,18 +496,24 @@ impl Voronoi {
                 x = (x1 + x3) / 2.0;
                 y = (y1 + y3) / 2.0;
             } else {
-                // let d = 1.0 / ab;
-                // x = x1 + (ey * bl - dy * cl) * d;
-                // y = y1 + (dx * cl - ex * bl) * d;
-                x = (x1 + x2 + x3) / 3.0;
-                y = (y1 + y2 + y3) / 3.0;
+                let corners = [(x1, y1), (x2, y2), (x3, y3)];
+                let (vx, vy) = match mode {
+                    VertexMode::Centroid => ((x1 + x2 + x3) / 3.0, (y1 + y2 + y3) / 3.0),
+                    VertexMode::Circumcenter => circumcenter(corners, ab),
+                    VertexMode::Incenter => incenter(corners),
+                    VertexMode::ClampedCircumcenter => match obtuse_edge(corners) {
+                        Some(((ax, ay), (bx, by))) => ((ax + bx) / 2.0, (ay + by) / 2.0),
+                        None => circumcenter(corners, ab),
+                    },
+                };
+                x = vx;
+                y = vy;
             }
             circumcenters[j] = x;
             circumcenters[j + 1] = y;
             i += 3;
             j += 2;
         }
-        // TODO: exterior hull?
         circumcenters
     }
 
@@ -169,7 +523,7 @@ impl Voronoi {
         inedges: &Vec<usize>,
         halfedges: &Vec<usize>,
         triangles: &Vec<usize>,
-    ) -> Result<Adjacencies, String> {
+    ) -> Result<Adjacencies, TerrainError> {
         let mut adjacent = vec![Vec::new(); circumcenters.len() / 2];
         let mut voronoi_triangles = Vec::new();
         let mut voronoi_points = vec![Vec::new(); points.len() / 2];
@@ -178,7 +532,7 @@ impl Voronoi {
         for i in 0..inedges.len() {
             let e0 = inedges[i];
             if e0 == EMPTY {
-                return Err("Coincident point".to_string());
+                return Err(TerrainError::CoincidentPoint(i));
             } // coincident point
             let mut e = e0;
             let mut t;
@@ -212,7 +566,10 @@ impl Voronoi {
                 }
             }
 
-            voronoi_triangles.extend([i, e / 3, previous_t].iter());
+            // Close the fan, unless the cell is open to the outside of the hull.
+            if e != EMPTY {
+                voronoi_triangles.extend([i, e / 3, previous_t].iter());
+            }
         }
 
         Ok(Adjacencies {
@@ -226,15 +583,10 @@ impl Voronoi {
     fn get_neighbors(
         points: &Vec<f64>,
         inedges: &Vec<usize>,
-        hull: &Vec<usize>,
         halfedges: &Vec<usize>,
         triangles: &Vec<usize>,
     ) -> Vec<Vec<usize>> {
         let mut neighbors = vec![Vec::new(); points.len() / 2];
-        let mut hull_index = vec![EMPTY; points.len() / 2];
-        for i in 0..hull.len() {
-            hull_index[hull[i]] = i;
-        }
 
         for i in 0..points.len() / 2 {
             let e0 = inedges[i];
@@ -244,16 +596,17 @@ impl Voronoi {
             loop {
                 p0 = triangles[e];
                 neighbors[i].push(p0);
-                e = if e % 3 == 2 { e - 2 } else { e + 1 };
+                e = next_halfedge(e);
 
-                e = halfedges[e];
-                if e == EMPTY {
-                    let p = hull[(hull_index[i] + 1) % hull.len()];
+                if halfedges[e] == EMPTY {
+                    // `e` runs along the hull, out from `i` to its next hull point.
+                    let p = triangles[next_halfedge(e)];
                     if p != p0 {
                         neighbors[i].push(p);
                     }
                     break;
                 }
+                e = halfedges[e];
                 if e == e0 {
                     break;
                 }
@@ -262,3 +615,105 @@ impl Voronoi {
         neighbors
     }
 }
+
+pub(crate) fn next_halfedge(e: usize) -> usize {
+    if e % 3 == 2 {
+        e - 2
+    } else {
+        e + 1
+    }
+}
+
+// Sutherland–Hodgman, cutting `polygon` by each side of `bounds` in turn. Only the top and bottom
+// when `wrap` is set.
+fn clip_polygon(mut polygon: Vec<(f64, f64)>, bounds: [f64; 4], wrap: bool) -> Vec<(f64, f64)> {
+    let [xmin, ymin, xmax, ymax] = bounds;
+    // Each side as (axis, limit, keep below the limit).
+    let mut sides = vec![(1, ymin, false), (1, ymax, true)];
+    if !wrap {
+        sides.extend([(0, xmin, false), (0, xmax, true)].iter());
+    }
+
+    for &(axis, limit, below) in sides.iter() {
+        let coordinate = |p: (f64, f64)| if axis == 0 { p.0 } else { p.1 };
+        let inside = |p: (f64, f64)| {
+            if below {
+                coordinate(p) <= limit
+            } else {
+                coordinate(p) >= limit
+            }
+        };
+        let crossing = |p: (f64, f64), q: (f64, f64)| {
+            let t = (limit - coordinate(p)) / (coordinate(q) - coordinate(p));
+            (p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t)
+        };
+
+        let mut clipped = Vec::with_capacity(polygon.len() + 2);
+        for k in 0..polygon.len() {
+            let p = polygon[k];
+            let q = polygon[(k + 1) % polygon.len()];
+            match (inside(p), inside(q)) {
+                (true, true) => clipped.push(q),
+                (true, false) => clipped.push(crossing(p, q)),
+                (false, true) => {
+                    clipped.push(crossing(p, q));
+                    clipped.push(q);
+                }
+                (false, false) => {}
+            }
+        }
+        polygon = clipped;
+    }
+    polygon
+}
+
+fn circumcenter(corners: [(f64, f64); 3], ab: f64) -> (f64, f64) {
+    let [(x1, y1), (x2, y2), (x3, y3)] = corners;
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let (ex, ey) = (x3 - x1, y3 - y1);
+    let bl = dx * dx + dy * dy;
+    let cl = ex * ex + ey * ey;
+    let d = 1.0 / ab;
+    (x1 + (ey * bl - dy * cl) * d, y1 + (dx * cl - ex * bl) * d)
+}
+
+// The center of the largest circle inside the triangle, each corner weighted by the opposite side.
+fn incenter(corners: [(f64, f64); 3]) -> (f64, f64) {
+    let [(x1, y1), (x2, y2), (x3, y3)] = corners;
+    let a = libm::hypot(x3 - x2, y3 - y2);
+    let b = libm::hypot(x1 - x3, y1 - y3);
+    let c = libm::hypot(x2 - x1, y2 - y1);
+    let perimeter = a + b + c;
+    (
+        (a * x1 + b * x2 + c * x3) / perimeter,
+        (a * y1 + b * y2 + c * y3) / perimeter,
+    )
+}
+
+// The edge opposite an obtuse corner, if there is one. The circumcenter lies beyond it, and the
+// nearest point of the triangle to it is the middle of that edge.
+fn obtuse_edge(corners: [(f64, f64); 3]) -> Option<((f64, f64), (f64, f64))> {
+    (0..3).find_map(|i| {
+        let (p, q, r) = (corners[i], corners[(i + 1) % 3], corners[(i + 2) % 3]);
+        let dot = (q.0 - p.0) * (r.0 - p.0) + (q.1 - p.1) * (r.1 - p.1);
+        if dot < 0. {
+            Some((q, r))
+        } else {
+            None
+        }
+    })
+}
+
+// The twin of every halfedge in `triangles`, or `EMPTY` on the hull.
+fn pair_halfedges(triangles: &[usize]) -> Vec<usize> {
+    let mut edges = HashMap::new();
+    for e in 0..triangles.len() {
+        edges.insert((triangles[e], triangles[next_halfedge(e)]), e);
+    }
+    (0..triangles.len())
+        .map(|e| {
+            let key = (triangles[next_halfedge(e)], triangles[e]);
+            *edges.get(&key).unwrap_or(&EMPTY)
+        })
+        .collect()
+}
@@ -0,0 +1,48 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::terrain_generator::{TerrainGenerator, World};
+
+const SEA_LEVEL: f64 = 0.39;
+
+fn world(bathymetry: Option<&str>) -> World {
+    let mut gen = TerrainGenerator::new(Some(8));
+    gen.set_domain(2., 1., 1.).unwrap();
+    gen.set_bathymetry_js(bathymetry.map(String::from)).unwrap();
+    gen.world(0.03, SEA_LEVEL).unwrap()
+}
+
+fn deepest(world: &World) -> f64 {
+    world.layers()["depth"].iter().cloned().fold(0., f64::max)
+}
+
+#[test]
+fn sea_floor_deepens_away_from_the_coast() {
+    let plain = world(None);
+    let shaped = world(Some(
+        r#"{ "roughness": 0, "trenches": 0, "seamounts": 0, "abyssalDepth": 0.3 }"#,
+    ));
+    assert!(plain.layers().get("depth").is_none());
+    assert_eq!(shaped.coast_lines(), plain.coast_lines());
+
+    let depths = &shaped.layers()["depth"];
+    for (&depth, &height) in depths.iter().zip(shaped.cell_heights()) {
+        if height < SEA_LEVEL {
+            assert!(depth > 0.);
+        } else {
+            assert_eq!(depth, 0.);
+        }
+    }
+    let deepest = deepest(&shaped);
+    assert!(deepest > 0.1 && deepest <= 0.3 + 1e-9, "{}", deepest);
+}
+
+#[test]
+fn trenches_go_deeper_than_the_plain() {
+    let world = world(Some(
+        r#"{ "roughness": 0, "trenches": 2, "trenchDepth": 0.2, "seamounts": 0 }"#,
+    ));
+    assert!(deepest(&world) > 0.3, "{}", deepest(&world));
+
+    let mut gen = TerrainGenerator::new(None);
+    let invalid = gen.set_bathymetry_js(Some(r#"{ "shelfWidth": 0 }"#.to_string()));
+    assert!(matches!(invalid, Err(TerrainError::InvalidBathymetry(_))));
+}
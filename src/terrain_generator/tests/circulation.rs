@@ -0,0 +1,47 @@
+use terrain_generator::circulation::prevailing_wind;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const SEA_LEVEL: f64 = 0.39;
+
+#[test]
+fn prevailing_winds_follow_the_latitude_bands() {
+    // North east trades, south east trades, westerlies and polar easterlies.
+    let (east, north) = prevailing_wind(15.);
+    assert!(east < 0. && north < 0.);
+    let (east, north) = prevailing_wind(-15.);
+    assert!(east < 0. && north > 0.);
+    let (east, north) = prevailing_wind(45.);
+    assert!(east > 0. && north > 0.);
+    assert!(prevailing_wind(-75.).0 < 0.);
+    assert!(prevailing_wind(0.).0.abs() < 1e-12);
+}
+
+#[test]
+fn currents_run_along_the_coast() {
+    let mut gen = TerrainGenerator::new(Some(8));
+    gen.set_circulation(true, 60., 0., 1., 8);
+    let world = gen.world(0.03, SEA_LEVEL).unwrap();
+    let circulation = world.circulation().unwrap();
+    let points = &world.voronoi().delaunay.points;
+    let heights = world.cell_heights();
+    assert_eq!(circulation.wind.len(), points.len());
+
+    let (mut total, mut inland) = (0., 0.);
+    for (i, neighbors) in world.voronoi().delaunay.neighbors.iter().enumerate() {
+        let current = &circulation.currents[i * 2..i * 2 + 2];
+        if heights[i] >= SEA_LEVEL {
+            assert_eq!(current, [0., 0.]);
+            continue;
+        }
+        total += libm::hypot(current[0], current[1]);
+        for &j in neighbors.iter().filter(|&&j| heights[j] >= SEA_LEVEL) {
+            let (x, y) = (
+                points[j * 2] - points[i * 2],
+                points[j * 2 + 1] - points[i * 2 + 1],
+            );
+            inland += f64::max(0., (current[0] * x + current[1] * y) / libm::hypot(x, y));
+        }
+    }
+    assert!(total > 0.);
+    assert!(inland < 0.01 * total, "{} of {}", inland, total);
+}
@@ -0,0 +1,62 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const SEA_LEVEL: f64 = 0.39;
+
+#[test]
+fn contours_follow_the_height_field() {
+    let world = TerrainGenerator::new(Some(14))
+        .world(0.04, SEA_LEVEL)
+        .unwrap();
+    let contours = world.contours(SEA_LEVEL, 0.025, 4).unwrap();
+    assert!(contours.iter().any(|c| c.height == SEA_LEVEL && c.index));
+    assert!(contours.iter().any(|c| c.closed));
+
+    for contour in contours.iter() {
+        let steps = ((contour.height - SEA_LEVEL) / 0.025).round() as i64;
+        assert_eq!(contour.index, steps % 4 == 0);
+        assert!(contour.points.len() >= 4);
+        for p in contour.points.chunks_exact(2) {
+            let height = world
+                .height_at(p[0], p[1])
+                .unwrap_or_else(|| panic!("{:?}", p));
+            assert!(
+                (height - contour.height).abs() < 1e-6,
+                "{:?}: {} on the {} contour",
+                p,
+                height,
+                contour.height
+            );
+        }
+    }
+
+    assert!(matches!(
+        world.contours(SEA_LEVEL, 0., 4),
+        Err(TerrainError::InvalidContours { .. })
+    ));
+    assert!(matches!(
+        world.contours(SEA_LEVEL, 1e-6, 4),
+        Err(TerrainError::InvalidContours { .. })
+    ));
+}
+
+#[test]
+fn contours_break_at_the_seam() {
+    let mut gen = TerrainGenerator::new(Some(14));
+    gen.set_wrap(true);
+    let world = gen.world(0.04, SEA_LEVEL).unwrap();
+    for contour in world.contours(SEA_LEVEL, 0.05, 2).unwrap() {
+        for pair in contour
+            .points
+            .chunks_exact(2)
+            .collect::<Vec<_>>()
+            .windows(2)
+        {
+            assert!((pair[0][0] - pair[1][0]).abs() < 0.5);
+        }
+        assert!(contour
+            .points
+            .chunks_exact(2)
+            .all(|p| (0. ..=1.).contains(&p[0])));
+    }
+}
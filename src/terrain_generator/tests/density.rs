@@ -0,0 +1,81 @@
+use std::rc::Rc;
+
+use terrain_generator::density::Density;
+use terrain_generator::domain::Domain;
+use terrain_generator::error::TerrainError;
+use terrain_generator::pipeline::PipelineState;
+use terrain_generator::raster::Raster;
+use terrain_generator::stage::PoissonStage;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const RADIUS: f64 = 0.04;
+
+fn points(density: impl FnOnce(&mut TerrainGenerator)) -> Vec<f64> {
+    let mut gen = TerrainGenerator::new(Some(4));
+    density(&mut gen);
+    let mut state = PipelineState::new(
+        RADIUS,
+        0.39,
+        Domain::default(),
+        vec![Box::new(PoissonStage)],
+    );
+    state.run(&mut gen).unwrap();
+    state.context.points
+}
+
+// Points in the left and right halves of the map.
+fn halves(points: &[f64]) -> (usize, usize) {
+    let left = points.chunks_exact(2).filter(|p| p[0] < 0.5).count();
+    (left, points.len() / 2 - left)
+}
+
+#[test]
+fn default_density_follows_the_noise() {
+    assert_eq!(points(|_| ()), points(|gen| gen.density_noise()));
+    assert_ne!(points(|_| ()), points(|gen| gen.density_uniform()));
+}
+
+#[test]
+fn denser_where_the_spacing_is_smaller() {
+    let (left, right) = halves(&points(|gen| gen.density_uniform()));
+    assert!(
+        left.abs_diff(right) * 10 < left + right,
+        "{} {}",
+        left,
+        right
+    );
+
+    let mask = points(|gen| gen.density_mask(4, 1, vec![0.4, 0.4, 1., 1.]).unwrap());
+    let custom =
+        points(|gen| gen.density = Density::Custom(Rc::new(|x, _| if x < 0.5 { 0.4 } else { 1. })));
+    for points in [mask, custom] {
+        let (left, right) = halves(&points);
+        assert!(left > 2 * right, "{} {}", left, right);
+    }
+
+    let near = |points: &[f64]| {
+        points
+            .chunks_exact(2)
+            .filter(|p| libm::hypot(p[0] - 0.5, p[1] - 0.5) < 0.15)
+            .count()
+    };
+    let radial = points(|gen| gen.density_radial(0.5, 0.5, 0.3, 0.4));
+    let uniform = points(|gen| gen.density_uniform());
+    assert!(near(&radial) > 2 * near(&uniform));
+}
+
+#[test]
+fn spacing_above_one_is_as_sparse_as_uniform() {
+    let sparse = points(|gen| gen.density = Density::Custom(Rc::new(|_, _| 3.)));
+    assert_eq!(sparse, points(|gen| gen.density_uniform()));
+}
+
+#[test]
+fn rasters_too_big_to_count_are_errors() {
+    match Raster::new(usize::MAX, 2, vec![0.; 2]) {
+        Err(e @ TerrainError::InvalidRaster { .. }) => {
+            assert!(e.to_string().contains("too big"))
+        }
+        other => panic!("{:?}", other),
+    }
+}
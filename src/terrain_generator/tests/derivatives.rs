@@ -0,0 +1,72 @@
+use terrain_generator::derivatives::{Derivatives, Lighting, Nodes};
+use terrain_generator::domain::Domain;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+// Heights from `f(x, y)` at the points of a generated map.
+fn surface<F: Fn(f64, f64) -> f64>(f: F) -> (Vec<f64>, Vec<Vec<usize>>, Vec<f64>) {
+    let world = TerrainGenerator::new(Some(4)).world(0.05, 0.39).unwrap();
+    let delaunay = &world.voronoi().delaunay;
+    let heights = delaunay
+        .points
+        .chunks_exact(2)
+        .map(|p| f(p[0], p[1]))
+        .collect();
+    (delaunay.points.clone(), delaunay.neighbors.clone(), heights)
+}
+
+fn derivatives(points: &[f64], neighbors: &[Vec<usize>], heights: &[f64]) -> Derivatives {
+    let nodes = Nodes {
+        flat: points,
+        round: None,
+        domain: Domain::default(),
+    };
+    Derivatives::new(&nodes, neighbors, heights, &Lighting::default())
+}
+
+#[test]
+fn planes_have_one_slope_and_no_curvature() {
+    // Rising to the east and, with y pointing south, to the north.
+    let (points, neighbors, heights) = surface(|x, y| 0.5 + 0.2 * x - 0.1 * y);
+    let derivatives = derivatives(&points, &neighbors, &heights);
+    for i in 0..heights.len() {
+        assert!((derivatives.slope[i] - libm::hypot(0.2, 0.1)).abs() < 1e-9);
+        // Facing down to the south west.
+        let aspect = libm::atan2(-0.2, -0.1).to_degrees() + 360.;
+        assert!((derivatives.aspect[i] - aspect).abs() < 1e-6);
+        assert!(derivatives.plan_curvature[i].abs() < 1e-6);
+        assert!(derivatives.profile_curvature[i].abs() < 1e-6);
+    }
+}
+
+#[test]
+fn domes_are_convex_and_lit_from_the_north_west() {
+    let (points, neighbors, heights) = surface(|x, y| 1. - (x - 0.5).powi(2) - (y - 0.5).powi(2));
+    let derivatives = derivatives(&points, &neighbors, &heights);
+    let (mut lit, mut shaded) = (0., 0.);
+    for (i, p) in points.chunks_exact(2).enumerate() {
+        let distance = libm::hypot(p[0] - 0.5, p[1] - 0.5);
+        if !(0.1..0.4).contains(&distance) {
+            continue;
+        }
+        assert!(derivatives.plan_curvature[i] > 0.);
+        assert!(derivatives.profile_curvature[i] > 0.);
+        // North west is up and to the left on the map.
+        if p[0] < 0.4 && p[1] < 0.4 {
+            lit = f64::max(lit, derivatives.hillshade[i]);
+        } else if p[0] > 0.6 && p[1] > 0.6 {
+            shaded = f64::max(shaded, derivatives.hillshade[i]);
+        }
+    }
+    assert!(lit > shaded, "{} {}", lit, shaded);
+
+    let mut gen = TerrainGenerator::new(Some(4));
+    gen.set_derivatives(true, 315., 45., 1.);
+    let world = gen.world(0.05, 0.39).unwrap();
+    let relief = world.relief().unwrap();
+    assert_eq!(relief.cells.slope.len(), world.cell_heights().len());
+    assert!(relief
+        .corners
+        .hillshade
+        .iter()
+        .all(|h| (0. ..=1.).contains(h)));
+}
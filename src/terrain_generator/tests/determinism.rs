@@ -0,0 +1,55 @@
+//! Golden hashes for the determinism contract described in `src/seed.rs`.
+//!
+//! If generation changes on purpose, regenerate the table by running
+//! `cargo test --test determinism print_hashes -- --ignored --nocapture` and pasting the output.
+
+use terrain_generator::seed::derive_seed;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const POINTS: u32 = 256;
+const SEA_LEVEL: f64 = 0.39;
+
+const GOLDEN: [(u64, u64); 4] = [
+    (0, 17562501258722298270),
+    (123456, 5531426856967612602),
+    (15043459, 6040108316196025731),
+    (u64::MAX, 14606986933092460780),
+];
+
+fn world_hash(seed: u64) -> u64 {
+    let radius = (500.0 / POINTS as f64).sqrt() / 10.0;
+    let mut gen = TerrainGenerator::new(Some(seed));
+    gen.world(radius, SEA_LEVEL).unwrap().hash()
+}
+
+#[test]
+fn golden_hashes() {
+    for &(seed, expected) in GOLDEN.iter() {
+        assert_eq!(
+            world_hash(seed),
+            expected,
+            "world for seed {} changed",
+            seed
+        );
+    }
+}
+
+#[test]
+fn same_seed_same_world() {
+    assert_eq!(world_hash(42), world_hash(42));
+}
+
+#[test]
+fn stage_seeds_are_independent() {
+    assert_ne!(derive_seed(1, "poisson"), derive_seed(1, "rivers"));
+    assert_ne!(derive_seed(1, "poisson"), derive_seed(2, "poisson"));
+    assert_eq!(derive_seed(1, "poisson"), derive_seed(1, "poisson"));
+}
+
+#[test]
+#[ignore]
+fn print_hashes() {
+    for &(seed, _) in GOLDEN.iter() {
+        println!("    ({}, {}),", seed, world_hash(seed));
+    }
+}
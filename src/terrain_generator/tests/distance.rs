@@ -0,0 +1,64 @@
+use terrain_generator::terrain_generator::{TerrainGenerator, World};
+
+const SEA_LEVEL: f64 = 0.39;
+
+fn world(distances: bool) -> World {
+    flooded(distances, SEA_LEVEL)
+}
+
+fn flooded(distances: bool, sea_level: f64) -> World {
+    let mut gen = TerrainGenerator::new(Some(19));
+    gen.set_distance_fields(distances, 0.05);
+    gen.world(0.03, sea_level).unwrap()
+}
+
+#[test]
+fn distance_fields_are_optional() {
+    assert!(world(false).layers().is_empty());
+    assert_eq!(world(true).layers().len(), 3);
+}
+
+#[test]
+fn coast_distance_is_signed_and_continuous() {
+    let world = world(true);
+    let coast = &world.layers()["coastDistance"];
+    let points = &world.voronoi().delaunay.points;
+    let heights = world.cell_heights();
+
+    for (i, neighbors) in world.voronoi().delaunay.neighbors.iter().enumerate() {
+        assert_eq!(coast[i] > 0., heights[i] >= SEA_LEVEL, "cell {}", i);
+        for &j in neighbors.iter() {
+            let length = libm::hypot(
+                points[i * 2] - points[j * 2],
+                points[i * 2 + 1] - points[j * 2 + 1],
+            );
+            assert!((coast[i] - coast[j]).abs() <= length + 1e-9);
+        }
+    }
+}
+
+#[test]
+fn rivers_and_mountains_are_at_distance_zero() {
+    let world = world(true);
+    let layers = world.layers();
+    let heights = world.cell_heights();
+
+    let highest = (0..heights.len())
+        .max_by(|&a, &b| heights[a].total_cmp(&heights[b]))
+        .unwrap();
+    assert_eq!(layers["mountainDistance"][highest], 0.);
+    assert!(layers["mountainDistance"].iter().all(|d| d.is_finite()));
+
+    let river = &layers["riverDistance"];
+    assert!(river.iter().any(|&d| d == 0.));
+    assert!(river.iter().any(|&d| d > 0.));
+}
+
+#[test]
+fn nothing_to_reach_is_as_far_as_a_float_goes() {
+    let world = flooded(true, 2.);
+    let layers = world.layers();
+    assert!(layers["coastDistance"].iter().all(|&d| d == -f64::MAX));
+    assert!(layers["riverDistance"].iter().all(|&d| d == f64::MAX));
+    assert!(layers["mountainDistance"].iter().all(|&d| d == f64::MAX));
+}
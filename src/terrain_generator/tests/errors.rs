@@ -0,0 +1,112 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::stage::{default_stages, Stage, WorldContext};
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+#[test]
+fn stages_missing_from_the_list_are_errors() {
+    let mut gen = TerrainGenerator::new(Some(1));
+    let empty = gen.world_with_stages(0.1, 0.39, Vec::new());
+    assert!(matches!(empty, Err(TerrainError::MissingStage("voronoi"))));
+
+    let stages = gen
+        .stages()
+        .into_iter()
+        .filter(|stage| stage.name() != "voronoi")
+        .collect();
+    let no_voronoi = gen.world_with_stages(0.1, 0.39, stages);
+    assert!(matches!(
+        no_voronoi,
+        Err(TerrainError::MissingStage("voronoi"))
+    ));
+}
+
+// Stands in for the poisson stage with points the triangulation can't use.
+struct FixedPoints(Vec<f64>);
+
+impl Stage for FixedPoints {
+    fn name(&self) -> &str {
+        "points"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        context.points = self.0.clone();
+        Ok(())
+    }
+}
+
+fn world_from_points(points: Vec<f64>) -> Result<(), TerrainError> {
+    let mut stages = default_stages();
+    stages[0] = Box::new(FixedPoints(points));
+    TerrainGenerator::new(Some(1))
+        .world_with_stages(0.1, 0.39, stages)
+        .map(|_| ())
+}
+
+#[test]
+fn duplicate_and_collinear_points_are_errors() {
+    let points = vec![0., 0., 1., 0., 0., 1., 1., 1., 0.5, 0.5, 1., 0.];
+    assert!(matches!(
+        world_from_points(points),
+        Err(TerrainError::CoincidentPoint(_))
+    ));
+
+    let line = (0..5).flat_map(|i| [i as f64, 0.]).collect();
+    assert!(matches!(
+        world_from_points(line),
+        Err(TerrainError::Triangulation)
+    ));
+}
+
+#[test]
+fn radius_out_of_range_is_an_error() {
+    let mut gen = TerrainGenerator::new(Some(1));
+    for radius in [0., -0.1, f64::NAN, 2.] {
+        assert!(matches!(
+            gen.world(radius, 0.39),
+            Err(TerrainError::InvalidRadius(_))
+        ));
+    }
+    assert!(matches!(
+        gen.planet(0., 0.39),
+        Err(TerrainError::InvalidRadius(_))
+    ));
+}
+
+// Spoils one height before erosion, as a buggy custom stage might.
+struct NanStage;
+
+impl Stage for NanStage {
+    fn name(&self) -> &str {
+        "nan"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        context.heights[3] = f64::NAN;
+        Ok(())
+    }
+}
+
+#[test]
+fn nan_heights_are_errors() {
+    let mut gen = TerrainGenerator::new(Some(1));
+    let mut stages = default_stages();
+    let erosion = stages
+        .iter()
+        .position(|stage| stage.name() == "erosion")
+        .unwrap();
+    stages.insert(erosion, Box::new(NanStage));
+    assert!(matches!(
+        gen.world_with_stages(0.1, 0.39, stages),
+        Err(TerrainError::NanHeight(_))
+    ));
+}
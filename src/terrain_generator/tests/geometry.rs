@@ -0,0 +1,75 @@
+use std::f64::consts::PI;
+
+use terrain_generator::terrain_generator::{TerrainGenerator, World};
+
+fn world(wrap: bool) -> World {
+    let mut gen = TerrainGenerator::new(Some(11));
+    gen.set_domain(2., 1., 1.).unwrap();
+    gen.set_wrap(wrap);
+    gen.world(0.05, 0.39).unwrap()
+}
+
+#[test]
+fn areas_cover_the_map() {
+    for &wrap in [false, true].iter() {
+        let world = world(wrap);
+        let total = world.geometry().areas.iter().sum::<f64>();
+        assert!((total - 2.).abs() < 1e-9, "wrap {}: {}", wrap, total);
+
+        let geometry = world.geometry();
+        let on_map = geometry.areas.iter().map(|&area| area > 0.);
+        for (centroid, _) in geometry
+            .centroids
+            .chunks_exact(2)
+            .zip(on_map)
+            .filter(|c| c.1)
+        {
+            assert!(
+                (0. ..=2.).contains(&centroid[0]),
+                "wrap {}: {:?}",
+                wrap,
+                centroid
+            );
+            assert!(
+                (0. ..=1.).contains(&centroid[1]),
+                "wrap {}: {:?}",
+                wrap,
+                centroid
+            );
+        }
+    }
+}
+
+#[test]
+fn shared_edges_match_from_both_sides() {
+    let world = world(false);
+    let geometry = world.geometry();
+    let voronoi = world.voronoi();
+    let mut inside = 0;
+    for (i, neighbors) in voronoi.delaunay.neighbors.iter().enumerate() {
+        for &j in neighbors.iter() {
+            let (a, b) = (
+                geometry.edge_length(voronoi, i, j).unwrap(),
+                geometry.edge_length(voronoi, j, i).unwrap(),
+            );
+            assert_eq!(a, b);
+            if a > 0. {
+                inside += 1;
+            }
+        }
+        // Every edge of a cell inside the map is shared with a neighbour.
+        let shared = geometry.edge_lengths[i].iter().sum::<f64>();
+        if geometry.areas[i] > 0. && shared > 0. {
+            assert!(shared <= geometry.perimeters[i] + 1e-9);
+        }
+    }
+    assert!(inside > 0);
+}
+
+#[test]
+fn planet_areas_cover_the_sphere() {
+    let mut gen = TerrainGenerator::new(Some(3));
+    let world = gen.planet(0.1, 0.39).unwrap();
+    let total = world.geometry().areas.iter().sum::<f64>();
+    assert!((total - 4. * PI).abs() < 1e-6, "{}", total);
+}
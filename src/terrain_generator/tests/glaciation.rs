@@ -0,0 +1,64 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::terrain_generator::{TerrainGenerator, World};
+
+const SEA_LEVEL: f64 = 0.39;
+
+fn world(glaciation: Option<&str>) -> World {
+    let mut gen = TerrainGenerator::new(Some(8));
+    gen.set_domain(2., 1., 1.).unwrap();
+    gen.set_glaciation_js(glaciation.map(String::from)).unwrap();
+    gen.world(0.02, SEA_LEVEL).unwrap()
+}
+
+fn sea(world: &World) -> usize {
+    world
+        .cell_heights()
+        .iter()
+        .filter(|&&height| height < SEA_LEVEL)
+        .count()
+}
+
+#[test]
+fn ice_gathers_towards_the_poles() {
+    let plain = world(None);
+    assert!(plain.layers().get("ice").is_none());
+
+    let world = world(Some("{}"));
+    let ice = &world.layers()["ice"];
+    assert!(ice.iter().all(|&cover| (0. ..=1.).contains(&cover)));
+    let (mut polar, mut tropical) = ((0., 0), (0., 0));
+    let points = &world.voronoi().delaunay.points;
+    for (&cover, point) in ice.iter().zip(points.chunks_exact(2)) {
+        let y = point[1];
+        if !(0.1..=0.9).contains(&y) {
+            polar = (polar.0 + cover, polar.1 + 1);
+        } else if (0.4..=0.6).contains(&y) {
+            tropical = (tropical.0 + cover, tropical.1 + 1);
+        }
+    }
+    let polar = polar.0 / polar.1 as f64;
+    let tropical = tropical.0 / tropical.1 as f64;
+    assert!(
+        polar > 0.5 && polar > 5. * tropical,
+        "{} {}",
+        polar,
+        tropical
+    );
+}
+
+#[test]
+fn glaciers_cut_fjords_into_the_coast() {
+    let plain = world(None);
+    let northern = world(Some(r#"{ "north": 80, "south": 55 }"#));
+    assert!(
+        sea(&northern) > sea(&plain),
+        "{} {}",
+        sea(&northern),
+        sea(&plain)
+    );
+    assert!(northern.coast_lines().len() > plain.coast_lines().len());
+
+    let mut gen = TerrainGenerator::new(None);
+    let invalid = gen.set_glaciation_js(Some(r#"{ "width": 0 }"#.to_string()));
+    assert!(matches!(invalid, Err(TerrainError::InvalidGlaciation(_))));
+}
@@ -0,0 +1,80 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::raster::Raster;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const SEA_LEVEL: f64 = 0.39;
+const SIZE: usize = 16;
+
+// A hill in the middle of the map, sea all around.
+fn hill() -> Vec<u8> {
+    let mut pixels = Vec::new();
+    for row in 0..SIZE {
+        for col in 0..SIZE {
+            let dx = col as f64 / (SIZE - 1) as f64 - 0.5;
+            let dy = row as f64 / (SIZE - 1) as f64 - 0.5;
+            let height = (1. - 2. * libm::hypot(dx, dy)).max(0.);
+            pixels.push((height * 255.).round() as u8);
+        }
+    }
+    pixels
+}
+
+fn encode(pixels: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, SIZE as u32, SIZE as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(pixels).unwrap();
+    writer.finish().unwrap();
+    bytes
+}
+
+#[test]
+fn png_and_raw_import_the_same() {
+    let pixels = hill();
+    let from_png = Raster::from_png(&encode(&pixels)).unwrap();
+    assert_eq!(from_png, Raster::from_raw(SIZE, SIZE, &pixels, 8).unwrap());
+
+    let wide: Vec<u8> = pixels.iter().flat_map(|&p| [p, p]).collect();
+    let from_wide = Raster::from_raw(SIZE, SIZE, &wide, 16).unwrap();
+    assert!((from_wide.sample(0.5, 0.5) - from_png.sample(0.5, 0.5)).abs() < 1e-9);
+
+    assert!(matches!(
+        Raster::from_png(&pixels),
+        Err(TerrainError::InvalidHeightmap(_))
+    ));
+    assert!(matches!(
+        Raster::from_raw(SIZE, SIZE, &pixels, 12),
+        Err(TerrainError::InvalidHeightmap(_))
+    ));
+    assert!(matches!(
+        Raster::from_raw(usize::MAX / 2, 2, &wide, 16),
+        Err(TerrainError::InvalidHeightmap(_))
+    ));
+}
+
+#[test]
+fn heightmap_replaces_the_noise() {
+    let mut gen = TerrainGenerator::new(Some(5));
+    let noise = gen.heightmap_js(vec![0.3, 0.7], None);
+    gen.import_heightmap(&encode(&hill()), 0.).unwrap();
+    let world = gen.world(0.05, SEA_LEVEL).unwrap();
+
+    let points = &world.voronoi().delaunay.points;
+    for (p, &height) in points.chunks_exact(2).zip(world.cell_heights()) {
+        let distance = libm::hypot(p[0] - 0.5, p[1] - 0.5);
+        if distance > 0.4 {
+            assert!(height < SEA_LEVEL, "{:?}: {}", p, height);
+        } else if distance < 0.15 {
+            assert!(height > SEA_LEVEL, "{:?}: {}", p, height);
+        }
+    }
+
+    // Keeping some noise changes the map, and clearing the heightmap goes back to noise alone.
+    gen.import_heightmap(&encode(&hill()), 0.5).unwrap();
+    let blended = gen.heightmap_js(vec![0.3, 0.7], None);
+    gen.clear_heightmap();
+    assert_ne!(blended, noise);
+    assert_eq!(gen.heightmap_js(vec![0.3, 0.7], None), noise);
+}
@@ -0,0 +1,84 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::masks::{apply_masks, Mask};
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const SEA_LEVEL: f64 = 0.39;
+
+// A ring of points just inside the edge of the unit square, and a few in the middle.
+fn points() -> (Vec<f64>, Vec<f64>) {
+    let mut edge = Vec::new();
+    for i in 0..20 {
+        let t = i as f64 / 20.;
+        edge.extend([t, 0.005, t, 0.995, 0.005, t, 0.995, t].iter());
+    }
+    let middle = vec![0.45, 0.5, 0.5, 0.45, 0.55, 0.55, 0.5, 0.5];
+    (edge, middle)
+}
+
+#[test]
+fn ocean_border_keeps_land_off_the_edge() {
+    let (edge, middle) = points();
+    let mut gen = TerrainGenerator::new(Some(21));
+    let unmasked = gen.heightmap_js(middle.clone(), None);
+
+    gen.add_mask_js(r#"{ "type": "oceanBorder", "width": 0.2 }"#, 1.)
+        .unwrap();
+    assert!(gen.heightmap_js(edge, None).iter().all(|&h| h < SEA_LEVEL));
+    assert_eq!(gen.heightmap_js(middle, None), unmasked);
+    assert!(gen.world(0.08, SEA_LEVEL).is_ok());
+}
+
+#[test]
+fn masks_shape_and_blend() {
+    let (edge, middle) = points();
+    let mut gen = TerrainGenerator::new(Some(21));
+    let noise = |points: &[f64]| vec![0.6; points.len() / 2];
+
+    gen.add_mask_js(
+        r#"{ "type": "polygon", "points": [[0.3, 0.3], [0.7, 0.3], [0.7, 0.7], [0.3, 0.7]] }"#,
+        1.,
+    )
+    .unwrap();
+    assert_eq!(apply_masks(&middle, noise(&middle), &gen), noise(&middle));
+    assert!(apply_masks(&edge, noise(&edge), &gen)
+        .iter()
+        .all(|&h| h == 0.));
+
+    gen.clear_masks();
+    gen.add_image_mask(2, 2, vec![0.; 4], 0.5).unwrap();
+    assert!(apply_masks(&edge, noise(&edge), &gen)
+        .iter()
+        .all(|&h| h == 0.3));
+
+    let radial = Mask::Radial {
+        x: 0.5,
+        y: 0.5,
+        radius: 0.3,
+    };
+    assert_eq!(radial.values(&[0.5, 0.5, 0.9, 0.9], &gen), vec![1., 0.]);
+    let continents = Mask::Continents {
+        count: 3,
+        radius: 0.2,
+    };
+    let values = continents.values(&edge, &gen);
+    assert_eq!(values, continents.values(&edge, &gen));
+    assert!(values.iter().all(|v| (0. ..=1.).contains(v)));
+}
+
+#[test]
+fn invalid_masks_are_rejected() {
+    let mut gen = TerrainGenerator::new(Some(21));
+    for json in [
+        r#"{ "type": "oceanBorder", "width": 0 }"#,
+        r#"{ "type": "polygon", "points": [[0, 0], [1, 1]] }"#,
+        r#"{ "type": "image" }"#,
+    ]
+    .iter()
+    {
+        match gen.add_mask_js(json, 1.) {
+            Err(TerrainError::InvalidMask(_)) => {}
+            other => panic!("{}: {:?}", json, other),
+        }
+    }
+    assert!(gen.masks.is_empty());
+}
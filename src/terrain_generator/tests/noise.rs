@@ -0,0 +1,149 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::noise::NoiseNode;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+// Uses every kind of node.
+const GRAPH: &str = r#"{
+    "type": "select",
+    "threshold": 0.1,
+    "falloff": 0.2,
+    "mask": { "type": "perlin", "seed": "mask", "frequency": 0.5 },
+    "a": {
+        "type": "add",
+        "sources": [
+            { "type": "simplex", "seed": "base", "octaves": 4 },
+            { "type": "scaleBias", "scale": 0.2, "bias": 0.1,
+              "source": { "type": "cellular", "seed": "craters", "output": "CellValue" } }
+        ]
+    },
+    "b": {
+        "type": "blend",
+        "a": { "type": "ridged", "source": { "type": "value", "seed": "ridges" } },
+        "b": { "type": "terrace", "steps": 4,
+               "source": { "type": "billow", "source": { "type": "simplex" } } },
+        "factor": {
+            "type": "warp",
+            "strength": 0.5,
+            "angle": { "type": "constant", "value": 1.0 },
+            "length": { "type": "abs", "source": { "type": "simplex", "seed": "warp" } },
+            "source": {
+                "type": "multiply",
+                "sources": [
+                    { "type": "clamp", "min": -0.5, "max": 0.5, "source": { "type": "simplex" } },
+                    { "type": "curve", "points": [[1, 1], [-1, 0], [0, 0.2]],
+                      "source": { "type": "scale", "factor": 2.0, "source": { "type": "simplex" } } }
+                ]
+            }
+        }
+    }
+}"#;
+
+#[test]
+fn graphs_are_read_from_json() {
+    let mut gen = TerrainGenerator::new(Some(4));
+    let classic = gen.noise_single(0.3, 0.6);
+    gen.set_noise_graph_js(Some(GRAPH.to_string())).unwrap();
+    let custom = gen.noise_single(0.3, 0.6);
+    assert!(custom.is_finite() && custom != classic);
+
+    gen.set_noise_graph_js(None).unwrap();
+    assert_eq!(gen.noise_single(0.3, 0.6), classic);
+    gen.set_noise_graph(&NoiseNode::default()).unwrap();
+    assert_eq!(gen.noise_single(0.3, 0.6), classic);
+}
+
+#[test]
+fn custom_graphs_wrap_and_generate() {
+    let mut gen = TerrainGenerator::new(Some(4));
+    gen.set_domain(2., 1., 1.).unwrap();
+    gen.set_wrap(true);
+    gen.set_noise_graph_js(Some(GRAPH.to_string())).unwrap();
+    for i in 0..=10 {
+        let y = i as f64 / 10.;
+        assert!((gen.noise_single(0., y) - gen.noise_single(2., y)).abs() < 1e-4);
+    }
+    assert!(gen.world(0.08, 0.39).is_ok());
+}
+
+#[test]
+fn invalid_graphs_are_rejected() {
+    let mut gen = TerrainGenerator::new(Some(4));
+    for json in [
+        r#"{ "type": "terrace", "steps": 1, "source": { "type": "simplex" } }"#,
+        r#"{ "type": "add", "sources": [] }"#,
+        r#"{ "type": "simplex", "octaves": 0 }"#,
+        r#"{ "type": "nonsense" }"#,
+    ]
+    .iter()
+    {
+        match gen.set_noise_graph_js(Some(json.to_string())) {
+            Err(TerrainError::InvalidNoiseGraph(_)) => {}
+            other => panic!("{}: {:?}", json, other),
+        }
+    }
+}
+
+fn samples(gen: &TerrainGenerator) -> Vec<f64> {
+    (0..400)
+        .map(|i| {
+            let i = i as f64;
+            gen.noise_single(i * 0.618_034 % 1., i * 0.414_214 % 1.)
+        })
+        .collect()
+}
+
+#[test]
+fn ridges_and_cells_stay_in_range() {
+    let mut gen = TerrainGenerator::new(Some(9));
+    gen.set_domain(2., 1., 1.).unwrap();
+    gen.set_wrap(true);
+    for graph in [
+        r#"{ "type": "ridgedMultifractal", "octaves": 6, "frequency": 2 }"#,
+        r#"{ "type": "worley", "frequency": 4 }"#,
+        r#"{ "type": "worley", "frequency": 4, "output": "f2MinusF1" }"#,
+        r#"{ "type": "worley", "frequency": 4, "output": "crater" }"#,
+        r#"{ "type": "worley", "frequency": 4, "output": "mesa", "jitter": 0.5 }"#,
+    ]
+    .iter()
+    {
+        gen.set_noise_graph_js(Some(graph.to_string())).unwrap();
+        let samples = samples(&gen);
+        assert!(
+            samples.iter().all(|&n| (-0.01..=1.01).contains(&n)),
+            "{}",
+            graph
+        );
+        assert!(samples.iter().any(|&n| n != samples[0]), "{}", graph);
+        for i in 0..=10 {
+            let y = i as f64 / 10.;
+            let seam = (gen.noise_single(0., y) - gen.noise_single(2., y)).abs();
+            assert!(seam < 1e-3, "{} {}", graph, seam);
+        }
+    }
+}
+
+#[test]
+fn mix_by_elevation_leaves_low_ground_alone() {
+    let base = r#"{ "type": "simplex", "seed": "base", "octaves": 3 }"#;
+    let mixed = format!(
+        r#"{{ "type": "mix", "low": 0.2, "high": 0.6, "amount": 0.5, "base": {},
+              "source": {{ "type": "ridgedMultifractal", "octaves": 5, "frequency": 3 }} }}"#,
+        base
+    );
+    let mut gen = TerrainGenerator::new(Some(9));
+    gen.set_noise_graph_js(Some(base.to_string())).unwrap();
+    let before = samples(&gen);
+    gen.set_noise_graph_js(Some(mixed)).unwrap();
+    let after = samples(&gen);
+
+    let mut changed = 0;
+    for (&a, &b) in before.iter().zip(after.iter()) {
+        // `noise_single` maps -1..1 to 0..1, so 0.2 is 0.6 here.
+        if a <= 0.6 {
+            assert_eq!(a, b);
+        } else if a != b {
+            changed += 1;
+        }
+    }
+    assert!(changed > 0);
+}
@@ -0,0 +1,62 @@
+use terrain_generator::pipeline::Pipeline;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const RADIUS: f64 = 0.05;
+const SEA_LEVEL: f64 = 0.39;
+
+fn generator() -> TerrainGenerator {
+    let mut gen = TerrainGenerator::new(Some(11));
+    gen.set_domain(2., 1., 1.).unwrap();
+    gen.set_bathymetry_js(Some("{}".to_string())).unwrap();
+    gen
+}
+
+#[test]
+fn steps_one_erosion_iteration_at_a_time() {
+    let mut pipeline = generator().pipeline(RADIUS, SEA_LEVEL);
+    let mut stages = Vec::new();
+    let mut erosion = Vec::new();
+    while !pipeline.is_done() {
+        let stage = pipeline.stage();
+        if stage == "erosion" {
+            erosion.push(pipeline.iteration());
+        } else {
+            assert_eq!(pipeline.iteration(), 0);
+        }
+        if stages.last() != Some(&stage) {
+            stages.push(stage);
+        }
+        let more = pipeline.step().unwrap();
+        assert_eq!(more, !pipeline.is_done());
+    }
+    assert_eq!(pipeline.stage(), "done");
+    assert_eq!(erosion, (0..10).collect::<Vec<_>>());
+    assert_eq!(stages[..3], ["points", "voronoi", "heights"]);
+    assert!(stages.iter().any(|stage| stage == "bathymetry"));
+    assert!(!pipeline.step().unwrap());
+}
+
+#[test]
+fn finished_pipeline_matches_the_world() {
+    let mut gen = generator();
+    let stepped = gen.pipeline(RADIUS, SEA_LEVEL);
+    let world = gen.world(RADIUS, SEA_LEVEL).unwrap();
+    let finished = stepped.finish().unwrap();
+    assert!(finished.layers().contains_key("depth"));
+    assert_eq!(finished.hash(), world.hash());
+
+    let mut halfway = gen.pipeline(RADIUS, SEA_LEVEL);
+    for _ in 0..8 {
+        halfway.step().unwrap();
+    }
+    assert_eq!(halfway.finish().unwrap().hash(), world.hash());
+
+    let planet = gen.planet_pipeline(0.1, SEA_LEVEL).finish().unwrap();
+    assert_eq!(planet.hash(), gen.planet(0.1, SEA_LEVEL).unwrap().hash());
+
+    let mut plain = TerrainGenerator::new(Some(11));
+    let world = Pipeline::new(Some(11), RADIUS, SEA_LEVEL).finish().unwrap();
+    assert_eq!(world.hash(), plain.world(RADIUS, SEA_LEVEL).unwrap().hash());
+    let planet = Pipeline::planet(Some(11), 0.1, SEA_LEVEL).finish().unwrap();
+    assert_eq!(planet.hash(), plain.planet(0.1, SEA_LEVEL).unwrap().hash());
+}
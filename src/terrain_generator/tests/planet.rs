@@ -0,0 +1,48 @@
+use terrain_generator::domain::Domain;
+use terrain_generator::pipeline::PipelineState;
+use terrain_generator::stage::planet_stages;
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const RADIUS: f64 = 0.2;
+const SEA_LEVEL: f64 = 0.39;
+
+#[test]
+fn planet_mesh_is_closed() {
+    let mut gen = TerrainGenerator::new(Some(3));
+    let mut state = PipelineState::new(RADIUS, SEA_LEVEL, Domain::default(), planet_stages());
+    state.run(&mut gen).unwrap();
+
+    let sphere = state.context.sphere.as_ref().unwrap();
+    let voronoi = state.context.voronoi.as_ref().unwrap();
+    let n = sphere.positions.len() / 3;
+
+    // A closed mesh of triangles on a sphere always has 2n - 4 of them.
+    assert_eq!(voronoi.delaunay.triangles.len() / 3, 2 * n - 4);
+    assert!(voronoi.delaunay.hull.is_empty());
+    assert!(voronoi
+        .delaunay
+        .halfedges
+        .iter()
+        .all(|&e| e < voronoi.delaunay.triangles.len()));
+
+    for p in sphere.positions.chunks_exact(3) {
+        assert!(((p[0] * p[0] + p[1] * p[1] + p[2] * p[2]) - 1.).abs() < 1e-9);
+    }
+    assert_eq!(sphere.lat_lon.len(), n * 2);
+    assert_eq!(
+        state.context.heights.len(),
+        voronoi.delaunay.triangles.len() / 3
+    );
+}
+
+#[test]
+fn same_seed_same_planet() {
+    let hash = |seed| {
+        TerrainGenerator::new(Some(seed))
+            .planet(RADIUS, SEA_LEVEL)
+            .unwrap()
+            .hash()
+    };
+    assert_eq!(hash(3), hash(3));
+    assert_ne!(hash(3), hash(4));
+}
@@ -0,0 +1,101 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use terrain_generator::domain::Domain;
+use terrain_generator::error::TerrainError;
+use terrain_generator::glaciation::Glaciation;
+use terrain_generator::pipeline::PipelineState;
+use terrain_generator::stage::{DistanceStage, GlaciationStage, RelaxStage, Stage};
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const RADIUS: f64 = 0.1;
+const SEA_LEVEL: f64 = 0.39;
+
+type Reports = Rc<RefCell<Vec<(String, f64)>>>;
+
+fn reporting() -> (TerrainGenerator, Reports) {
+    let mut gen = TerrainGenerator::new(Some(6));
+    let reports = Reports::default();
+    let log = reports.clone();
+    gen.on_progress(move |stage, fraction| log.borrow_mut().push((stage.to_string(), fraction)));
+    (gen, reports)
+}
+
+#[test]
+fn progress_goes_through_every_stage() {
+    let (mut gen, reports) = reporting();
+    gen.world(RADIUS, SEA_LEVEL).unwrap();
+
+    let reports = reports.borrow();
+    assert_eq!(reports.first().unwrap(), &("points".to_string(), 0.));
+    assert_eq!(reports.last().unwrap(), &("done".to_string(), 1.));
+    assert!(reports.iter().all(|(_, f)| (0. ..=1.).contains(f)));
+    let erosion = reports
+        .iter()
+        .filter(|(stage, _)| stage == "erosion")
+        .map(|&(_, f)| f)
+        .collect::<Vec<_>>();
+    assert_eq!(erosion, (0..10).map(|i| i as f64 / 10.).collect::<Vec<_>>());
+}
+
+#[test]
+fn cancelling_from_the_callback_stops_generation() {
+    let mut gen = TerrainGenerator::new(Some(6));
+    let token = gen.cancellation_token();
+    gen.on_progress(move |stage, _| {
+        if stage == "erosion" {
+            token.cancel();
+        }
+    });
+    assert_eq!(gen.world(RADIUS, SEA_LEVEL), Err(TerrainError::Cancelled));
+
+    // Too late to cancel once every stage has run.
+    let mut gen = TerrainGenerator::new(Some(6));
+    let token = gen.cancellation_token();
+    gen.on_progress(move |stage, _| {
+        if stage == "done" {
+            token.cancel();
+        }
+    });
+    assert!(gen.world(RADIUS, SEA_LEVEL).is_ok());
+    assert!(gen.cancellation_token().is_cancelled());
+    gen.cancellation_token().reset();
+    gen.world(RADIUS, SEA_LEVEL).unwrap();
+}
+
+#[test]
+fn cancelling_between_pipeline_steps() {
+    let gen = TerrainGenerator::new(Some(6));
+    let mut pipeline = gen.pipeline(RADIUS, SEA_LEVEL);
+    for _ in 0..3 {
+        pipeline.step().unwrap();
+    }
+    gen.cancellation_token().cancel();
+    assert_eq!(pipeline.step(), Err(TerrainError::Cancelled));
+}
+
+#[test]
+fn cancelling_stops_long_stages_part_way() {
+    let stages: Vec<Box<dyn Stage>> = vec![
+        Box::new(RelaxStage::default()),
+        Box::new(GlaciationStage {
+            settings: Glaciation::default(),
+        }),
+        Box::new(DistanceStage::default()),
+    ];
+    for mut stage in stages {
+        let mut gen = TerrainGenerator::new(Some(6));
+        gen.set_relaxation(1, 1., true);
+        gen.set_glaciation_js(Some("{}".to_string())).unwrap();
+        gen.set_distance_fields(true, 0.05);
+        let mut state = PipelineState::new(RADIUS, SEA_LEVEL, Domain::default(), gen.stages());
+        while state.stage_name() != stage.name() {
+            state.step(&mut gen).unwrap();
+        }
+
+        // As if cancelled from elsewhere once the stage had started.
+        gen.cancellation_token().cancel();
+        let cancelled = stage.run(&mut state.context, &mut gen, 0);
+        assert_eq!(cancelled, Err(TerrainError::Cancelled), "{}", stage.name());
+    }
+}
@@ -0,0 +1,65 @@
+use terrain_generator::domain::Domain;
+use terrain_generator::pipeline::PipelineState;
+use terrain_generator::stage::{PoissonStage, RelaxStage, Stage};
+use terrain_generator::terrain_generator::TerrainGenerator;
+use terrain_generator::voronoi::{VertexMode, Voronoi};
+
+const RADIUS: f64 = 0.05;
+
+fn points(stages: Vec<Box<dyn Stage>>) -> Vec<f64> {
+    let mut gen = TerrainGenerator::new(Some(5));
+    gen.density_uniform();
+    let mut state = PipelineState::new(RADIUS, 0.39, Domain::default(), stages);
+    state.run(&mut gen).unwrap();
+    state.context.points
+}
+
+// Coefficient of variation of the areas of the cells inside the map.
+fn irregularity(points: Vec<f64>) -> f64 {
+    let mut voronoi = Voronoi::new(points, VertexMode::Circumcenter).unwrap();
+    voronoi.clip([0.2, 0.2, 0.8, 0.8], false);
+    let areas = voronoi
+        .cell_polygons
+        .iter()
+        .filter(|polygon| polygon.len() >= 6 && polygon.iter().all(|&x| x > 0.2 && x < 0.8))
+        .map(|polygon| {
+            let n = polygon.len() / 2;
+            (0..n)
+                .map(|k| {
+                    let (x0, y0) = (polygon[k * 2], polygon[k * 2 + 1]);
+                    let (x1, y1) = (polygon[(k + 1) % n * 2], polygon[(k + 1) % n * 2 + 1]);
+                    x0 * y1 - x1 * y0
+                })
+                .sum::<f64>()
+                .abs()
+                / 2.
+        })
+        .collect::<Vec<_>>();
+    let mean = areas.iter().sum::<f64>() / areas.len() as f64;
+    let variance = areas.iter().map(|a| (a - mean).powi(2)).sum::<f64>() / areas.len() as f64;
+    variance.sqrt() / mean
+}
+
+#[test]
+fn relaxing_evens_out_cells() {
+    let relax = RelaxStage {
+        iterations: 5,
+        strength: 1.,
+        weighted: false,
+    };
+    let before = irregularity(points(vec![Box::new(PoissonStage)]));
+    let after = irregularity(points(vec![Box::new(PoissonStage), Box::new(relax)]));
+    assert!(after < before * 0.75, "{} -> {}", before, after);
+}
+
+#[test]
+fn relaxation_is_off_by_default() {
+    let radius = 0.1;
+    let mut gen = TerrainGenerator::new(Some(5));
+    let plain = gen.world(radius, 0.39).unwrap().hash();
+    gen.set_relaxation(2, 0.5, true);
+    let relaxed = gen.world(radius, 0.39).unwrap().hash();
+    assert_ne!(plain, relaxed);
+    gen.set_relaxation(0, 0.5, true);
+    assert_eq!(plain, gen.world(radius, 0.39).unwrap().hash());
+}
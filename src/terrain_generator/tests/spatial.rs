@@ -0,0 +1,76 @@
+use terrain_generator::sphere;
+use terrain_generator::terrain_generator::{TerrainGenerator, World};
+
+fn world(wrap: bool) -> World {
+    let mut gen = TerrainGenerator::new(Some(17));
+    gen.set_domain(2., 1., 1.).unwrap();
+    gen.set_wrap(wrap);
+    gen.world(0.05, 0.39).unwrap()
+}
+
+// Deterministic positions spread over the 2 × 1 map.
+fn queries() -> impl Iterator<Item = (f64, f64)> {
+    (0..500).map(|i| {
+        let i = i as f64;
+        ((i * 0.618_034 % 1.) * 2., i * 0.414_214 % 1.)
+    })
+}
+
+#[test]
+fn cell_at_finds_the_nearest_point() {
+    for &wrap in [false, true].iter() {
+        let world = world(wrap);
+        let points = &world.voronoi().delaunay.points;
+        for (x, y) in queries() {
+            let distance = |p: usize| {
+                let mut dx = (points[p * 2] - x).abs();
+                if wrap {
+                    dx = dx.min(2. - dx);
+                }
+                dx.hypot(points[p * 2 + 1] - y)
+            };
+            let nearest = (0..points.len() / 2)
+                .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+                .unwrap();
+            let cell = world.cell_at(x, y).unwrap();
+            assert!(
+                distance(cell) <= distance(nearest) + 1e-12,
+                "wrap {}: ({}, {})",
+                wrap,
+                x,
+                y
+            );
+        }
+        assert_eq!(world.cell_at(-0.1, 0.5), None);
+        assert_eq!(world.height_at(0.5, 1.1), None);
+    }
+}
+
+#[test]
+fn height_at_a_point_is_its_cell_height() {
+    let world = world(false);
+    let points = &world.voronoi().delaunay.points;
+    let mut checked = 0;
+    for (p, point) in points.chunks_exact(2).enumerate() {
+        if point.iter().all(|&x| x > 0.05) && point[0] < 1.95 && point[1] < 0.95 {
+            let height = world.height_at(point[0], point[1]).unwrap();
+            assert!((height - world.cell_heights()[p]).abs() < 1e-9);
+            checked += 1;
+        }
+    }
+    assert!(checked > 100);
+}
+
+#[test]
+fn planet_cells_are_found_from_the_map() {
+    let mut gen = TerrainGenerator::new(Some(3));
+    let world = gen.planet(0.1, 0.39).unwrap();
+    let points = &world.voronoi().delaunay.points;
+    for p in (0..points.len() / 2).step_by(7) {
+        let (x, y) = (points[p * 2], points[p * 2 + 1]);
+        assert_eq!(world.cell_at(x, y), Some(p));
+        assert!(world.height_at(x, y).unwrap().is_finite());
+    }
+    let (x, y) = sphere::map_position(&sphere::from_map_position(0.3, 0.7));
+    assert!((x - 0.3).abs() < 1e-12 && (y - 0.7).abs() < 1e-12);
+}
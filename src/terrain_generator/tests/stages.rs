@@ -0,0 +1,47 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::stage::{default_stages, Stage, WorldContext};
+use terrain_generator::terrain_generator::TerrainGenerator;
+
+const SEA_LEVEL: f64 = 0.39;
+
+// Marks land cells, so it needs to run after the cell heights and before anything that wants it.
+struct LandStage;
+
+impl Stage for LandStage {
+    fn name(&self) -> &str {
+        "land"
+    }
+
+    fn run(
+        &mut self,
+        context: &mut WorldContext,
+        _gen: &mut TerrainGenerator,
+        _iteration: usize,
+    ) -> Result<(), TerrainError> {
+        assert!(!context.cell_heights.is_empty());
+        assert!(context.rivers.is_empty());
+        let land = context
+            .cell_heights
+            .iter()
+            .map(|&height| if height >= context.sea_level { 1. } else { 0. })
+            .collect();
+        context.layers.insert("land".to_string(), land);
+        Ok(())
+    }
+}
+
+#[test]
+fn custom_stages_run_between_built_in_ones() {
+    let mut gen = TerrainGenerator::new(Some(9));
+    let mut stages = default_stages();
+    let rivers = stages
+        .iter()
+        .position(|stage| stage.name() == "rivers")
+        .unwrap();
+    stages.insert(rivers, Box::new(LandStage));
+    let world = gen.world_with_stages(0.05, SEA_LEVEL, stages).unwrap();
+
+    let land = &world.layers()["land"];
+    assert!(land.contains(&0.) && land.contains(&1.));
+    assert!(land.iter().all(|&land| land == 0. || land == 1.));
+}
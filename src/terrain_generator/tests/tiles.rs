@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use terrain_generator::pipeline::PipelineState;
+use terrain_generator::stage::{tile_stages, WorldContext};
+use terrain_generator::terrain_generator::TerrainGenerator;
+use terrain_generator::tile::Tile;
+
+const RADIUS: f64 = 0.1;
+const SEA_LEVEL: f64 = 0.39;
+
+fn tile(gen: &mut TerrainGenerator, x: i32, y: i32) -> WorldContext {
+    let mut state = PipelineState::new(RADIUS, SEA_LEVEL, gen.domain, tile_stages(Tile::new(x, y)));
+    state.run(gen).unwrap();
+    state.context
+}
+
+// Height of every voronoi corner, by position.
+fn corners(context: &WorldContext) -> HashMap<(u64, u64), f64> {
+    let voronoi = context.voronoi.as_ref().unwrap();
+    voronoi
+        .circumcenters
+        .chunks_exact(2)
+        .zip(context.heights.iter())
+        .map(|(c, &height)| ((c[0].to_bits(), c[1].to_bits()), height))
+        .collect()
+}
+
+#[test]
+fn neighbouring_tiles_share_their_edge() {
+    let mut gen = TerrainGenerator::new(Some(11));
+    let left = corners(&tile(&mut gen, 0, 0));
+    let right = corners(&tile(&mut gen, 1, 0));
+
+    let shared = left
+        .iter()
+        .filter_map(|(corner, height)| right.get(corner).map(|other| (*height, *other)))
+        .collect::<Vec<_>>();
+    assert!(shared.len() > 10);
+    for (a, b) in shared {
+        assert_eq!(a, b);
+    }
+}
+
+#[test]
+fn only_eroded_heights_depend_on_tile_order() {
+    let mut first = TerrainGenerator::new(Some(11));
+    let before = tile(&mut first, 0, 0);
+    let after = tile(&mut first, 1, 0);
+    let mut second = TerrainGenerator::new(Some(11));
+    let alone = tile(&mut second, 1, 0);
+
+    // The mesh comes from the points alone, and the first tile has no one to take after.
+    assert_eq!(after.voronoi, alone.voronoi);
+    let mut third = TerrainGenerator::new(Some(11));
+    assert_eq!(tile(&mut third, 0, 0).heights, before.heights);
+
+    // The second tile takes its edge from the first, and erodes differently for it.
+    assert_ne!(after.heights, alone.heights);
+    let left = corners(&before);
+    let pinned = corners(&after)
+        .into_iter()
+        .filter(|(corner, _)| left.contains_key(corner))
+        .collect::<Vec<_>>();
+    assert!(pinned
+        .iter()
+        .all(|(corner, height)| left[corner] == *height));
+    let unpinned = corners(&alone);
+    assert!(pinned
+        .iter()
+        .any(|(corner, _)| unpinned[corner] != left[corner]));
+}
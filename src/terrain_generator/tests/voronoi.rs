@@ -0,0 +1,93 @@
+use terrain_generator::voronoi::{VertexMode, Voronoi};
+
+fn points() -> Vec<f64> {
+    // Scattered with a small LCG, which makes plenty of thin triangles.
+    let mut state: u64 = 12345;
+    let mut random = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    (0..200).map(|_| random() * 10.).collect()
+}
+
+fn corners(voronoi: &Voronoi, t: usize) -> [(f64, f64); 3] {
+    let p = &voronoi.delaunay.points;
+    let i = |k: usize| voronoi.delaunay.triangles[t * 3 + k] * 2;
+    [0, 1, 2].map(|k| (p[i(k)], p[i(k) + 1]))
+}
+
+fn vertex(voronoi: &Voronoi, t: usize) -> (f64, f64) {
+    (
+        voronoi.circumcenters[t * 2],
+        voronoi.circumcenters[t * 2 + 1],
+    )
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+#[test]
+fn circumcenters_are_equidistant() {
+    let voronoi = Voronoi::new(points(), VertexMode::Circumcenter).unwrap();
+    for t in 0..voronoi.delaunay.triangles.len() / 3 {
+        let c = vertex(&voronoi, t);
+        let [a, b, d] = corners(&voronoi, t).map(|p| distance(c, p));
+        assert!((a - b).abs() < 1e-9 && (a - d).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn clamped_circumcenters_stay_inside() {
+    let voronoi = Voronoi::new(points(), VertexMode::ClampedCircumcenter).unwrap();
+    for t in 0..voronoi.delaunay.triangles.len() / 3 {
+        let c = vertex(&voronoi, t);
+        let [a, b, d] = corners(&voronoi, t);
+        let side =
+            |p: (f64, f64), q: (f64, f64)| (q.0 - p.0) * (c.1 - p.1) - (q.1 - p.1) * (c.0 - p.0);
+        let sides = [side(a, b), side(b, d), side(d, a)];
+        assert!(sides.iter().all(|&s| s >= -1e-9) || sides.iter().all(|&s| s <= 1e-9));
+    }
+}
+
+#[test]
+fn default_is_centroid() {
+    let centroid = Voronoi::new(points(), VertexMode::Centroid).unwrap();
+    let default = Voronoi::new(points(), VertexMode::default()).unwrap();
+    assert_eq!(centroid, default);
+    let [a, b, d] = corners(&centroid, 0);
+    let c = vertex(&centroid, 0);
+    assert!(distance(c, ((a.0 + b.0 + d.0) / 3., (a.1 + b.1 + d.1) / 3.)) < 1e-12);
+}
+
+fn area(polygon: &[f64]) -> f64 {
+    let n = polygon.len() / 2;
+    let mut twice = 0.;
+    for k in 0..n {
+        let (x0, y0) = (polygon[k * 2], polygon[k * 2 + 1]);
+        let (x1, y1) = (polygon[(k + 1) % n * 2], polygon[(k + 1) % n * 2 + 1]);
+        twice += x0 * y1 - x1 * y0;
+    }
+    twice.abs() / 2.
+}
+
+#[test]
+fn clipped_cells_cover_the_map() {
+    for &mode in [VertexMode::Circumcenter, VertexMode::Centroid].iter() {
+        let mut voronoi = Voronoi::new(points(), mode).unwrap();
+        voronoi.clip([0., 0., 10., 10.], false);
+
+        assert_eq!(voronoi.cell_polygons.len(), 100);
+        let total: f64 = voronoi.cell_polygons.iter().map(|p| area(p)).sum();
+        assert!(
+            (total - 100.).abs() < 1e-6,
+            "{:?} cells cover {}",
+            mode,
+            total
+        );
+        for polygon in voronoi.cell_polygons.iter() {
+            assert!(polygon.len() >= 6);
+            assert!(polygon.iter().all(|&x| (0. ..=10.).contains(&x)));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use terrain_generator::error::TerrainError;
+use terrain_generator::pipeline::PipelineState;
+use terrain_generator::stage::default_stages;
+use terrain_generator::terrain_generator::TerrainGenerator;
+use terrain_generator::voronoi::{VertexMode, Voronoi};
+
+fn wrapping_generator() -> TerrainGenerator {
+    let mut gen = TerrainGenerator::new(Some(7));
+    gen.set_domain(3., 1., 1.).unwrap();
+    gen.set_wrap(true);
+    gen
+}
+
+#[test]
+fn noise_matches_across_the_seam() {
+    let gen = wrapping_generator();
+    for i in 0..=10 {
+        let y = i as f64 / 10.;
+        let (west, east) = (gen.noise_single(0., y), gen.noise_single(3., y));
+        assert!((west - east).abs() < 1e-6, "seam at y = {}", y);
+    }
+}
+
+#[test]
+fn wrapping_world_generates() {
+    let radius = (500.0 * 3. / 512.0_f64).sqrt() / 10.0;
+    let mut gen = wrapping_generator();
+    let wrapped = gen.world(radius, 0.39).unwrap();
+    assert_eq!(wrapped.hash(), gen.world(radius, 0.39).unwrap().hash());
+}
+
+#[test]
+fn cells_and_coasts_cross_the_seam() {
+    let radius = 0.05;
+    let width = 3.;
+    let mut gen = wrapping_generator();
+    let mut state = PipelineState::new(radius, 0.39, gen.domain, default_stages());
+    state.run(&mut gen).unwrap();
+    let voronoi = state.context.voronoi().unwrap();
+    let points = &voronoi.delaunay.points;
+
+    // A cell closer to the seam than to any neighbour on its own side has neighbours across it.
+    let x = |i: usize| points[2 * i];
+    let distance = |i: usize, j: usize| {
+        let dx = (x(i) - x(j)).abs();
+        dx.min(width - dx)
+            .hypot(points[2 * i + 1] - points[2 * j + 1])
+    };
+    let mut at_seam = 0;
+    for i in 0..points.len() / 2 {
+        let neighbors = &voronoi.delaunay.neighbors[i];
+        let spacing = neighbors
+            .iter()
+            .map(|&j| distance(i, j))
+            .fold(f64::INFINITY, f64::min);
+        let to_seam = x(i).min(width - x(i));
+        if !(0. ..=1.).contains(&points[2 * i + 1]) || to_seam >= spacing {
+            continue;
+        }
+        at_seam += 1;
+        assert!(
+            neighbors
+                .iter()
+                .any(|&j| (x(i) - x(j)).abs() > width - 3. * spacing),
+            "cell {} at the seam has no neighbour across it",
+            i
+        );
+    }
+    assert!(at_seam > 10);
+
+    // Coasts only end at the top and bottom of the map, and some run straight over the seam.
+    let centers = &voronoi.circumcenters;
+    let mut ends = vec![0; centers.len() / 2];
+    for &(a, b) in &state.context.coast_lines {
+        ends[a] += 1;
+        ends[b] += 1;
+    }
+    for (c, &count) in ends.iter().enumerate() {
+        let (x, y) = (centers[2 * c], centers[2 * c + 1]);
+        assert!(
+            count % 2 == 0 || y < radius || y > 1. - radius,
+            "coast ends at ({}, {})",
+            x,
+            y
+        );
+    }
+    assert!(state
+        .context
+        .coast_lines
+        .iter()
+        .any(|&(a, b)| (centers[2 * a] - centers[2 * b]).abs() > width / 2.));
+}
+
+#[test]
+fn ragged_edges_are_an_error_rather_than_a_hang() {
+    let mut state: u64 = 12345;
+    let mut random = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let points = (0..200).map(|_| random() * 10.).collect();
+    assert!(matches!(
+        Voronoi::new_wrapping(points, 10., VertexMode::Centroid),
+        Err(TerrainError::Triangulation)
+    ));
+}